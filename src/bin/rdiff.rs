@@ -1,11 +1,21 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use console::{StyledObject, Term, style};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, MultiSelect};
-use rdiff::cli::{KeyVal, parse_key_val};
+use rdiff::cli::{KeyVal, ReportTarget, parse_key_val, parse_report};
 use rdiff::{
-    DiffConfig, DiffProfile, ExtraArgs, LoadConfig, RequestProfile, ResponseProfile, highlight_text,
+    ConfigFormat, DiffConfig, DiffProfile, DiffResult, EXIT_ERROR, EXIT_IDENTICAL,
+    EffectiveRequest, ExtraArgs, LoadConfig, ProfileRun, RequestProfile, ResponseProfile,
+    RunStatus, Side, config_schema, highlight_text, html_report, mask_secrets, render_table,
 };
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Instant;
 
 /// Diff two requests and compare the difference of responses.
 #[derive(Debug, Clone, Parser)]
@@ -19,42 +29,564 @@ pub struct Args {
 #[non_exhaustive]
 pub enum Action {
     /// Diff two API responses based on the given profile.
+    /// Exits with 0 if identical, 1 if different, 2 on errors and 3 if a request failed.
     Run(RunArgs),
-    Parse,
+    /// Build a config interactively and print it.
+    Parse(ParseArgs),
+    /// Print a profile after defaults, `extends` and templates were resolved.
+    Show(ShowArgs),
+    /// List the profiles of the config file, or describe the requests of one profile.
+    List(ListArgs),
+    /// Check the config file and print every error and warning with its location.
+    Validate(ValidateArgs),
+    /// Print the JSON Schema of the config file.
+    Schema,
+    /// Inspect the config file.
+    #[clap(subcommand)]
+    Config(ConfigAction),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ParseArgs {
+    /// Format of the printed config: yaml, json or toml.
+    #[clap(long, value_parser = ConfigFormat::from_str, default_value = "yaml")]
+    pub format: ConfigFormat,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ListArgs {
+    /// Print the requests of a profile as they are sent,
+    /// after defaults, templates and overrides were applied.
+    #[clap(short, long, value_parser)]
+    pub describe: Option<String>,
+
+    /// Print JSON instead of a table, for scripting.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Overrides args, the same as for `run`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ValidateArgs {
+    /// Overrides template variables with `-e '$key=value'`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigAction {
+    /// Print which config file is used and why.
+    Path(ConfigPathArgs),
 }
 
 #[derive(Debug, Clone, Parser)]
+pub struct ConfigPathArgs {
+    /// Path to the config file, or a directory of config files.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(group(ArgGroup::new("select").required(true).multiple(true).args(["profile", "all", "tag"])))]
 pub struct RunArgs {
-    /// Profile name.
+    /// Profile name. Glob patterns such as `user_*` run every matching profile as a batch.
     #[clap(short, long, value_parser)]
-    pub profile: String,
+    pub profile: Option<String>,
+
+    /// Run every profile in the config as a batch.
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// Run every profile with the given tag as a batch.
+    #[clap(short, long, value_parser)]
+    pub tag: Vec<String>,
+
+    /// Maximum number of profiles diffed at the same time in a batch.
+    #[clap(long, default_value_t = 4)]
+    pub concurrency: usize,
 
     /// Overrides args. Could be used to overrides params, headers and body of the request.
     /// For query params, use `-e key=value`.
     /// For headers, use `-e %key=value`.
-    /// For body, use `-e @key=value`.
+    /// For body, use `-e @key=value`, with a path like `@items[0].qty=value` for nested fields
+    /// and `@key:=value` for JSON values like numbers or arrays.
+    /// A value of `@./path` is read from a file, e.g. `-e @payload:=@./body.json`,
+    /// use `@@` for a value starting with a literal `@`, e.g. `-e @handle=@@rustlang`.
+    /// To remove a body field or header, use `-e @key-` or `-e %key-`.
+    /// For files of a multipart body, use `-e @key@./path`, optionally followed by
+    /// `;type=image/png` or `;filename=name.png`.
+    /// For template variables, use `-e '$key=value'`.
+    /// Prefix an override with `1:` or `2:` to apply it to one request only,
+    /// e.g. `-e 2:%authorization=...`.
     #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
     pub extra_params: Vec<KeyVal>,
 
-    /// Path to the YAML config file.
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
     #[clap(short, long, value_parser)]
     pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+
+    /// Write a report file after the run, e.g. `junit=report.xml` or `json=report.json`.
+    /// Could be given multiple times.
+    #[clap(short, long, value_parser=parse_report, number_of_values=1)]
+    pub report: Vec<ReportTarget>,
+
+    /// Output format of the diff.
+    #[clap(short, long, value_enum, default_value_t = Output::Terminal)]
+    pub output: Output,
+
+    /// Print retried requests.
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// Layout of the terminal diff.
+    #[clap(short, long, value_enum, default_value_t = Layout::Unified)]
+    pub layout: Layout,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ShowArgs {
+    /// Profile name.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// Overrides template variables with `-e '$key=value'`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub input_format: Option<ConfigFormat>,
+
+    /// Format of the printed profile: yaml, json or toml.
+    /// Defaults to the format of the config file.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// Unified diff with old and new line numbers.
+    Unified,
+    /// Two columns fitting the terminal width.
+    SideBySide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Colored diff for the terminal.
+    Terminal,
+    /// Structured diff result as JSON.
+    Json,
+    /// Self-contained HTML page with side-by-side diffs.
+    Html,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
-    match args.action {
-        Action::Run(args) => run(args).await?,
-        Action::Parse => parse().await?,
+    let result = match args.action {
+        Action::Run(args) => run(args).await,
+        Action::Parse(args) => parse(args).await.map(|_| EXIT_IDENTICAL),
+        Action::Show(args) => show(args).await.map(|_| EXIT_IDENTICAL),
+        Action::List(args) => list(args).await.map(|_| EXIT_IDENTICAL),
+        Action::Validate(args) => validate(args),
+        Action::Schema => schema().map(|_| EXIT_IDENTICAL),
+        Action::Config(ConfigAction::Path(args)) => config_path(args).map(|_| EXIT_IDENTICAL),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {}", mask_secrets(&format!("{:?}", e)));
+            ExitCode::from(EXIT_ERROR)
+        }
     }
+}
+
+async fn run(args: RunArgs) -> Result<u8> {
+    let config_file = DiffConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let config = DiffConfig::load(&config_file, args.format, &extra_args.vars).await?;
+
+    let batch = args.all || !args.tag.is_empty() || args.profile.as_deref().is_none_or(is_pattern);
+    let mut runs = if batch {
+        let names = if args.all {
+            config.select(&[], &[])?
+        } else {
+            config.select(&Vec::from_iter(args.profile), &args.tag)?
+        };
+        if names.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No profile selected in file {}",
+                &config_file
+            ));
+        }
+        config
+            .diff_all(&names, &extra_args, args.concurrency)
+            .await?
+    } else {
+        let profile_name = args.profile.unwrap_or_default();
+        let profile = config.get_profile(&profile_name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Profile {} not found in file {}",
+                profile_name,
+                &config_file
+            )
+        })?;
+        let start = Instant::now();
+        let result = profile.diff(extra_args).await;
+        vec![ProfileRun::new(profile_name, start.elapsed(), result)]
+    };
+
+    runs.iter_mut().for_each(ProfileRun::mask_secrets);
+
+    for report in &args.report {
+        tokio::fs::write(&report.path, report.format.render(&runs)?)
+            .await
+            .with_context(|| format!("failed to write report {}", report.path))?;
+    }
+
+    if batch {
+        print_batch(&runs, args.output, args.layout, args.verbose)?;
+    } else {
+        print_single(&runs[0], args.output, args.layout, args.verbose)?;
+    }
+
+    Ok(ProfileRun::exit_code(&runs))
+}
+
+fn print_single(run: &ProfileRun, output: Output, layout: Layout, verbose: bool) -> Result<()> {
+    if verbose {
+        print_retries(run);
+    }
+    match (&run.result, &run.error, output) {
+        (Some(result), _, Output::Terminal) => println!("{}", render(result, layout)?),
+        (Some(result), _, Output::Json) => println!("{}", serde_json::to_string_pretty(result)?),
+        (_, _, Output::Html) => println!("{}", html_report(std::slice::from_ref(run))?),
+        (_, Some(error), _) => eprintln!("Request failed: {}", error),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Print every profile run of a batch followed by a summary table.
+fn print_batch(runs: &[ProfileRun], output: Output, layout: Layout, verbose: bool) -> Result<()> {
+    match output {
+        Output::Terminal => {
+            for run in runs {
+                println!(
+                    "{} {} ({}ms)",
+                    styled_status(run.status),
+                    style(&run.name).bold(),
+                    run.elapsed.as_millis()
+                );
+                if verbose {
+                    print_retries(run);
+                }
+                match (&run.result, &run.error) {
+                    (Some(result), _) if !result.equal => println!("{}", render(result, layout)?),
+                    (_, Some(error)) => println!("{}\n", style(error).red()),
+                    _ => {}
+                }
+            }
+            print!("{}", summary_table(runs)?);
+        }
+        Output::Json => println!("{}", serde_json::to_string_pretty(runs)?),
+        Output::Html => println!("{}", html_report(runs)?),
+    }
+
+    Ok(())
+}
+
+fn print_retries(run: &ProfileRun) {
+    for retry in run.result.iter().flat_map(|r| &r.retries) {
+        eprintln!("{}", style(retry).dim());
+    }
+}
+
+fn render(result: &DiffResult, layout: Layout) -> Result<String> {
+    match layout {
+        Layout::Unified => result.render_terminal(),
+        Layout::SideBySide => {
+            let width = Term::stdout()
+                .size_checked()
+                .map_or(120, |(_, w)| w as usize);
+            result.render_side_by_side(width)
+        }
+    }
+}
+
+fn summary_table(runs: &[ProfileRun]) -> Result<String> {
+    let width = runs
+        .iter()
+        .map(|r| r.name.len())
+        .chain(std::iter::once("Profile".len()))
+        .max()
+        .unwrap_or_default();
+    let count = |status| runs.iter().filter(|r| r.status == status).count();
+
+    let mut output = String::new();
+    writeln!(
+        &mut output,
+        "{}",
+        style(format!(
+            "{:<width$}  {:<6}  {:>8}",
+            "Profile", "Status", "Time"
+        ))
+        .bold()
+    )?;
+    for run in runs {
+        writeln!(
+            &mut output,
+            "{:<width$}  {:<6}  {:>6}ms",
+            run.name,
+            styled_status(run.status),
+            run.elapsed.as_millis()
+        )?;
+    }
+    writeln!(
+        &mut output,
+        "\nTotal: {}, passed: {}, different: {}, failed: {}",
+        runs.len(),
+        count(RunStatus::Pass),
+        count(RunStatus::Diff),
+        count(RunStatus::Error)
+    )?;
+
+    Ok(output)
+}
+
+fn styled_status(status: RunStatus) -> StyledObject<String> {
+    let text = format!("{:<6}", status);
+    match status {
+        RunStatus::Pass => style(text).green(),
+        RunStatus::Diff => style(text).yellow(),
+        RunStatus::Error => style(text).red(),
+    }
+}
+
+/// Check whether a profile name contains glob characters.
+fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
+/// A profile as printed by `list --json`.
+#[derive(Debug, Serialize)]
+struct ListedProfile<'a> {
+    name: &'a str,
+    req1: ListedRequest,
+    req2: ListedRequest,
+    skip_headers: &'a [String],
+    skip_body: &'a [String],
+    tags: &'a [String],
+}
+
+#[derive(Debug, Serialize)]
+struct ListedRequest {
+    method: String,
+    url: String,
+}
+
+impl ListedRequest {
+    fn new(req: &RequestProfile, args: &ExtraArgs) -> Result<Self> {
+        Ok(Self {
+            method: req.method.to_string(),
+            url: req.get_url(args)?,
+        })
+    }
+}
+
+/// Requests of a profile as they are sent, and the rules applied to their responses.
+#[derive(Debug, Serialize)]
+struct DescribedProfile<'a> {
+    req1: EffectiveRequest,
+    req2: EffectiveRequest,
+    resp: &'a ResponseProfile,
+}
+
+/// Print a table of the profiles, or the effective requests of the described profile.
+async fn list(args: ListArgs) -> Result<()> {
+    let config_file = DiffConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let config = DiffConfig::load(&config_file, args.format, &extra_args.vars).await?;
+
+    if let Some(name) = &args.describe {
+        let profile = config.get_profile(name).ok_or_else(|| {
+            anyhow::anyhow!("Profile {} not found in file {}", name, &config_file)
+        })?;
+        let described = DescribedProfile {
+            req1: profile.req1.effective(&extra_args.side(Side::Req1))?,
+            req2: profile.req2.effective(&extra_args.side(Side::Req2))?,
+            resp: &profile.resp,
+        };
+        if args.json {
+            println!(
+                "{}",
+                mask_secrets(&serde_json::to_string_pretty(&described)?)
+            );
+        } else {
+            let result = serde_yaml::to_string(&described)?;
+            println!("{}", highlight_text(&mask_secrets(&result), "yaml", None)?);
+        }
+        return Ok(());
+    }
+
+    let names = config.select(&[], &[])?;
+    if args.json {
+        let profiles = names
+            .iter()
+            .map(|name| {
+                let profile = &config.profiles[name];
+                Ok(ListedProfile {
+                    name,
+                    req1: ListedRequest::new(&profile.req1, &extra_args.side(Side::Req1))?,
+                    req2: ListedRequest::new(&profile.req2, &extra_args.side(Side::Req2))?,
+                    skip_headers: &profile.resp.skip_headers,
+                    skip_body: &profile.resp.skip_body,
+                    tags: &profile.tags,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        println!(
+            "{}",
+            mask_secrets(&serde_json::to_string_pretty(&profiles)?)
+        );
+        return Ok(());
+    }
+
+    let rows = names
+        .iter()
+        .map(|name| {
+            let profile = &config.profiles[name];
+            let (method1, method2) = (&profile.req1.method, &profile.req2.method);
+            let method = if method1 == method2 {
+                method1.to_string()
+            } else {
+                format!("{}/{}", method1, method2)
+            };
+            Ok(vec![
+                name.clone(),
+                method,
+                profile.req1.get_url(&extra_args.side(Side::Req1))?,
+                profile.req2.get_url(&extra_args.side(Side::Req2))?,
+                skip_rules(&profile.resp),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let header = ["Profile", "Method", "URL 1", "URL 2", "Skip"];
+    print!("{}", mask_secrets(&render_table(&header, &rows)));
 
     Ok(())
 }
 
-async fn run(args: RunArgs) -> Result<()> {
-    let config_file = args.config.unwrap_or_else(|| "./rdiff.yaml".to_string());
-    let config = DiffConfig::load_yaml(&config_file).await?;
+/// Summarize the skipped headers and body paths of a profile.
+fn skip_rules(resp: &ResponseProfile) -> String {
+    let mut rules = Vec::new();
+    if !resp.skip_headers.is_empty() {
+        rules.push(format!("headers: {}", resp.skip_headers.join(", ")));
+    }
+    if !resp.skip_body.is_empty() {
+        rules.push(format!("body: {}", resp.skip_body.join(", ")));
+    }
+    if rules.is_empty() {
+        "-".to_string()
+    } else {
+        rules.join("; ")
+    }
+}
+
+/// Print every issue of the config, failing if any is an error.
+fn validate(args: ValidateArgs) -> Result<u8> {
+    let config_file = DiffConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let issues = DiffConfig::lint(&config_file, args.format, &extra_args.vars)?;
+    for issue in issues.iter() {
+        println!("{}", mask_secrets(&issue.styled()));
+    }
+    let (errors, warnings) = (issues.errors().count(), issues.warnings().count());
+    if issues.is_empty() {
+        println!("{} {} is valid", style("✓").green(), config_file);
+    } else {
+        println!("\nErrors: {}, warnings: {}", errors, warnings);
+    }
+
+    Ok(if errors > 0 {
+        EXIT_ERROR
+    } else {
+        EXIT_IDENTICAL
+    })
+}
+
+/// Print the JSON Schema of the config, e.g. for yaml-language-server.
+fn schema() -> Result<()> {
+    let schema = config_schema::<DiffConfig>();
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+
+    Ok(())
+}
+
+/// Print the config file found by the discovery order and why it was chosen.
+fn config_path(args: ConfigPathArgs) -> Result<()> {
+    let location = DiffConfig::discover(args.config.as_deref())?;
+    println!("{}", location.path.display());
+    println!("{}", style(format!("({})", location.source)).dim());
+
+    Ok(())
+}
+
+/// Print the resolved profile as YAML.
+async fn show(args: ShowArgs) -> Result<()> {
+    let config_file = DiffConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let config = DiffConfig::load(&config_file, args.input_format, &extra_args.vars).await?;
     let profile = config.get_profile(&args.profile).ok_or_else(|| {
         anyhow::anyhow!(
             "Profile {} not found in file {}",
@@ -63,16 +595,22 @@ async fn run(args: RunArgs) -> Result<()> {
         )
     })?;
 
-    let extra_args: ExtraArgs = args.extra_params.into();
-
-    let output = profile.diff(extra_args).await?;
-    println!("{}", output);
+    let format = args
+        .format
+        .or(args.input_format)
+        .or_else(|| ConfigFormat::from_path(Path::new(&config_file)))
+        .unwrap_or_default();
+    let result = format.render(&HashMap::from([(&args.profile, profile)]))?;
+    println!(
+        "{}",
+        highlight_text(&mask_secrets(&result), format.syntax(), None)?
+    );
 
     Ok(())
 }
 
 /// Parse config content from cli.
-async fn parse() -> Result<()> {
+async fn parse(args: ParseArgs) -> Result<()> {
     let theme = ColorfulTheme::default();
     let url1: String = Input::with_theme(&theme)
         .with_prompt("Url1")
@@ -104,8 +642,8 @@ async fn parse() -> Result<()> {
     let profile = DiffProfile::new(req1, req2, res);
     let config = DiffConfig::new(vec![(profile_name, profile)].into_iter().collect());
 
-    let result = serde_yaml::to_string(&config)?;
-    let highlighten_text = highlight_text(&result, "yaml", None)?;
+    let result = args.format.render(&config)?;
+    let highlighten_text = highlight_text(&result, args.format.syntax(), None)?;
     println!("{}", highlighten_text);
 
     Ok(())
@@ -1,13 +1,18 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use console::style;
 use dialoguer::Input;
 use dialoguer::theme::ColorfulTheme;
 use rdiff::cli::{KeyVal, parse_key_val};
 use rdiff::{
-    ExtraArgs, LoadConfig, RequestConfig, RequestProfile, get_body_text, get_headers_text,
-    get_status_text, highlight_text,
+    ConfigFormat, ExtraArgs, LoadConfig, RequestConfig, RequestProfile, config_schema,
+    get_body_text, get_headers_text, get_status_text, highlight_text, mask_secrets, render_table,
 };
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
 
 /// Diff two requests and compare the difference of responses.
 #[derive(Debug, Clone, Parser)]
@@ -22,7 +27,82 @@ pub struct Args {
 pub enum Action {
     /// Diff two API responses based on the given profile.
     Run(RunArgs),
-    Parse,
+    /// Build a config interactively and print it.
+    Parse(ParseArgs),
+    /// Print a profile after defaults, `extends` and templates were resolved.
+    Show(ShowArgs),
+    /// List the profiles of the config file, or describe the requests of one profile.
+    List(ListArgs),
+    /// Check the config file and print every error and warning with its location.
+    Validate(ValidateArgs),
+    /// Print the JSON Schema of the config file.
+    Schema,
+    /// Inspect the config file.
+    #[clap(subcommand)]
+    Config(ConfigAction),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ParseArgs {
+    /// Format of the printed config: yaml, json or toml.
+    #[clap(long, value_parser = ConfigFormat::from_str, default_value = "yaml")]
+    pub format: ConfigFormat,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ListArgs {
+    /// Print the requests of a profile as they are sent,
+    /// after defaults, templates and overrides were applied.
+    #[clap(short, long, value_parser)]
+    pub describe: Option<String>,
+
+    /// Print JSON instead of a table, for scripting.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Overrides args, the same as for `run`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ValidateArgs {
+    /// Overrides template variables with `-e '$key=value'`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigAction {
+    /// Print which config file is used and why.
+    Path(ConfigPathArgs),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ConfigPathArgs {
+    /// Path to the config file, or a directory of config files.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -34,13 +114,56 @@ pub struct RunArgs {
     /// Overrides args. Could be used to overrides params, headers and body of the request.
     /// For query params, use `-e key=value`.
     /// For headers, use `-e %key=value`.
-    /// For body, use `-e @key=value`.
+    /// For body, use `-e @key=value`, with a path like `@items[0].qty=value` for nested fields
+    /// and `@key:=value` for JSON values like numbers or arrays.
+    /// A value of `@./path` is read from a file, e.g. `-e @payload:=@./body.json`,
+    /// use `@@` for a value starting with a literal `@`, e.g. `-e @handle=@@rustlang`.
+    /// To remove a body field or header, use `-e @key-` or `-e %key-`.
+    /// For files of a multipart body, use `-e @key@./path`, optionally followed by
+    /// `;type=image/png` or `;filename=name.png`.
+    /// For template variables, use `-e '$key=value'`.
     #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
     pub extra_params: Vec<KeyVal>,
 
-    /// Path to the YAML config file.
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
     #[clap(short, long, value_parser)]
     pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
+
+    /// Print retried requests.
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ShowArgs {
+    /// Profile name.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// Overrides template variables with `-e '$key=value'`.
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Path to the config file, or a directory of config files.
+    /// Defaults to the discovered config, see `config path`.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Format of the config file: yaml, json or toml.
+    /// Detected from the file extension by default.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub input_format: Option<ConfigFormat>,
+
+    /// Format of the printed profile: yaml, json or toml.
+    /// Defaults to the format of the config file.
+    #[clap(long, value_parser = ConfigFormat::from_str)]
+    pub format: Option<ConfigFormat>,
 }
 
 #[tokio::main]
@@ -48,15 +171,24 @@ async fn main() -> Result<()> {
     let args = Args::parse();
     match args.action {
         Action::Run(args) => run(args).await?,
-        Action::Parse => parse().await?,
+        Action::Parse(args) => parse(args).await?,
+        Action::Show(args) => show(args).await?,
+        Action::List(args) => list(args).await?,
+        Action::Validate(args) => validate(args)?,
+        Action::Schema => schema()?,
+        Action::Config(ConfigAction::Path(args)) => config_path(args)?,
     }
 
     Ok(())
 }
 
 async fn run(args: RunArgs) -> Result<()> {
-    let config_file = args.config.unwrap_or_else(|| "./rdiff.yaml".to_string());
-    let config = RequestConfig::load_yaml(&config_file).await?;
+    let config_file = RequestConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args = request_args(args.extra_params)?;
+    let config = RequestConfig::load(&config_file, args.format, &extra_args.vars).await?;
     let profile = config.get_profile(&args.profile).ok_or_else(|| {
         anyhow::anyhow!(
             "Profile {} not found in file {}",
@@ -65,17 +197,22 @@ async fn run(args: RunArgs) -> Result<()> {
         )
     })?;
 
-    let extra_args: ExtraArgs = args.extra_params.into();
     let url = profile.get_url(&extra_args)?;
 
-    let resp = profile.send(&extra_args).await?.get_inner();
+    let resp = profile.send(&extra_args).await?;
+    if args.verbose {
+        for retry in resp.retries() {
+            eprintln!("{}", mask_secrets(retry));
+        }
+    }
+    let resp = resp.get_inner();
 
     let mut output = String::new();
     let status = get_status_text(&resp)?;
-    let headers = get_headers_text(&resp, &[])?;
-    let body = get_body_text(resp, &[]).await?;
+    let headers = mask_secrets(&get_headers_text(&resp, &[])?);
+    let body = mask_secrets(&get_body_text(resp, &[]).await?);
 
-    writeln!(&mut output, "Utl: {}\n", url)?;
+    writeln!(&mut output, "Utl: {}\n", mask_secrets(url.as_str()))?;
     writeln!(&mut output, "{}", status)?;
     writeln!(
         &mut output,
@@ -89,8 +226,164 @@ async fn run(args: RunArgs) -> Result<()> {
     Ok(())
 }
 
+/// A profile as printed by `list --json`.
+#[derive(Debug, Serialize)]
+struct ListedProfile<'a> {
+    name: &'a str,
+    method: String,
+    url: String,
+}
+
+/// Print a table of the profiles, or the effective request of the described profile.
+async fn list(args: ListArgs) -> Result<()> {
+    let config_file = RequestConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args = request_args(args.extra_params)?;
+    let config = RequestConfig::load(&config_file, args.format, &extra_args.vars).await?;
+
+    if let Some(name) = &args.describe {
+        let profile = config.get_profile(name).ok_or_else(|| {
+            anyhow::anyhow!("Profile {} not found in file {}", name, &config_file)
+        })?;
+        let described = profile.effective(&extra_args)?;
+        if args.json {
+            println!(
+                "{}",
+                mask_secrets(&serde_json::to_string_pretty(&described)?)
+            );
+        } else {
+            let result = serde_yaml::to_string(&described)?;
+            println!("{}", highlight_text(&mask_secrets(&result), "yaml", None)?);
+        }
+        return Ok(());
+    }
+
+    let mut names: Vec<&String> = config.profiles.keys().collect();
+    names.sort();
+    if args.json {
+        let profiles = names
+            .iter()
+            .map(|name| {
+                let profile = &config.profiles[*name];
+                Ok(ListedProfile {
+                    name,
+                    method: profile.method.to_string(),
+                    url: profile.get_url(&extra_args)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        println!(
+            "{}",
+            mask_secrets(&serde_json::to_string_pretty(&profiles)?)
+        );
+        return Ok(());
+    }
+
+    let rows = names
+        .iter()
+        .map(|name| {
+            let profile = &config.profiles[*name];
+            Ok(vec![
+                name.to_string(),
+                profile.method.to_string(),
+                profile.get_url(&extra_args)?,
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    print!(
+        "{}",
+        mask_secrets(&render_table(&["Profile", "Method", "URL"], &rows))
+    );
+
+    Ok(())
+}
+
+/// Print every issue of the config, failing if any is an error.
+fn validate(args: ValidateArgs) -> Result<()> {
+    let config_file = RequestConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let issues = RequestConfig::lint(&config_file, args.format, &extra_args.vars)?;
+    for issue in issues.iter() {
+        println!("{}", mask_secrets(&issue.styled()));
+    }
+    let (errors, warnings) = (issues.errors().count(), issues.warnings().count());
+    if issues.is_empty() {
+        println!("{} {} is valid", style("✓").green(), config_file);
+    } else {
+        println!("\nErrors: {}, warnings: {}", errors, warnings);
+    }
+
+    match errors {
+        0 => Ok(()),
+        n => Err(anyhow::anyhow!("{} has {} errors", config_file, n)),
+    }
+}
+
+/// Print the JSON Schema of the config, e.g. for yaml-language-server.
+fn schema() -> Result<()> {
+    let schema = config_schema::<RequestConfig>();
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+
+    Ok(())
+}
+
+/// Print the config file found by the discovery order and why it was chosen.
+fn config_path(args: ConfigPathArgs) -> Result<()> {
+    let location = RequestConfig::discover(args.config.as_deref())?;
+    println!("{}", location.path.display());
+    println!("{}", style(format!("({})", location.source)).dim());
+
+    Ok(())
+}
+
+/// Print the resolved profile as YAML.
+async fn show(args: ShowArgs) -> Result<()> {
+    let config_file = RequestConfig::discover(args.config.as_deref())?
+        .path
+        .display()
+        .to_string();
+    let extra_args: ExtraArgs = args.extra_params.into();
+    let config = RequestConfig::load(&config_file, args.input_format, &extra_args.vars).await?;
+    let profile = config.get_profile(&args.profile).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Profile {} not found in file {}",
+            args.profile,
+            &config_file
+        )
+    })?;
+
+    let format = args
+        .format
+        .or(args.input_format)
+        .or_else(|| ConfigFormat::from_path(Path::new(&config_file)))
+        .unwrap_or_default();
+    let result = format.render(&HashMap::from([(&args.profile, profile)]))?;
+    println!(
+        "{}",
+        highlight_text(&mask_secrets(&result), format.syntax(), None)?
+    );
+
+    Ok(())
+}
+
+/// Get the overrides of a request, which can't target a side of a diff.
+fn request_args(extra_params: Vec<KeyVal>) -> Result<ExtraArgs> {
+    let extra_args: ExtraArgs = extra_params.into();
+    if extra_args.has_sides() {
+        return Err(anyhow::anyhow!(
+            "`-e` with a `1:` or `2:` prefix is only supported by rdiff"
+        ));
+    }
+    Ok(extra_args)
+}
+
 /// Parse config content from cli.
-async fn parse() -> Result<()> {
+async fn parse(args: ParseArgs) -> Result<()> {
     let theme = ColorfulTheme::default();
     let url: String = Input::with_theme(&theme)
         .with_prompt("Url")
@@ -103,8 +396,8 @@ async fn parse() -> Result<()> {
 
     let config = RequestConfig::new(vec![(name, profile)].into_iter().collect());
 
-    let result = serde_yaml::to_string(&config)?;
-    let highlighten_text = highlight_text(&result, "yaml", None)?;
+    let result = args.format.render(&config)?;
+    let highlighten_text = highlight_text(&result, args.format.syntax(), None)?;
     println!("{}", highlighten_text);
 
     Ok(())
@@ -1,65 +1,354 @@
 use anyhow::{Result, anyhow};
+use serde_json::Value;
 
-use crate::ExtraArgs;
+use crate::{ExtraArgs, FilePart, JsonPath, ReportFormat, Side, SideArgs};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KeyValType {
     Query,
     Header,
     Body,
+    File,
+    Var,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct KeyVal {
+    /// Request of a diff the override applies to, or both if `None`.
+    side: Option<Side>,
     key_val_type: KeyValType,
     key: String,
-    value: String,
+    /// `None` removes the key.
+    value: Option<Value>,
 }
 
 /// Parse the key value pair from the command line arguments.
+/// Files are attached to a multipart body with `@key@path`,
+/// and body fields or headers are removed with `@key-` or `%key-`.
+/// A `1:` or `2:` prefix applies the override to one request of a diff only.
 pub fn parse_key_val(s: &str) -> Result<KeyVal> {
-    let mut parts = s.splitn(2, "=");
-    let key = parts
-        .next()
-        .ok_or_else(|| anyhow!("Invalid key value pair"))?
-        .trim();
-    let value = parts
-        .next()
-        .ok_or_else(|| anyhow!("Invalid key value pair"))?
-        .trim();
-
-    let (key_val_type, key) = match key.chars().next() {
-        Some('@') => (KeyValType::Body, &key[1..]),
-        Some('%') => (KeyValType::Header, &key[1..]),
-        Some(k) if k.is_alphabetic() => (KeyValType::Query, key),
-        _ => return Err(anyhow!("Invalid key value pair")),
+    let (side, s) = match s.split_once(':') {
+        Some(("1", s)) => (Some(Side::Req1), s),
+        Some(("2", s)) => (Some(Side::Req2), s),
+        _ => (None, s),
+    };
+    let key_val = parse_override(s)?;
+    if side.is_some() && key_val.key_val_type == KeyValType::Var {
+        return Err(anyhow!(
+            "Template variables apply to the whole config and can't have a `1:` or `2:` prefix"
+        ));
+    }
+    Ok(KeyVal { side, ..key_val })
+}
+
+fn parse_override(s: &str) -> Result<KeyVal> {
+    if let Some((key, file)) = parse_file(s) {
+        file.parse::<FilePart>()?;
+        return Ok(KeyVal {
+            side: None,
+            key_val_type: KeyValType::File,
+            key: key.to_string(),
+            value: Some(file.into()),
+        });
+    }
+
+    if let Some((key_val_type, key)) = parse_removal(s) {
+        return Ok(KeyVal {
+            side: None,
+            key_val_type,
+            key: key.to_string(),
+            value: None,
+        });
+    }
+
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid key value pair"))?;
+    let (key, value) = (key.trim(), value.trim());
+    // `@key:=value` sets a body field to a JSON value.
+    let (key, typed) = match key.strip_suffix(':') {
+        Some(key) => (key.trim_end(), true),
+        None => (key, false),
+    };
+
+    let (key_val_type, key) = parse_key(key)?;
+    let value = match key_val_type {
+        KeyValType::Body => {
+            key.parse::<JsonPath>()?;
+            body_value(value, typed)?
+        }
+        _ if typed => {
+            return Err(anyhow!(
+                "Typed values with `:=` are only supported for body fields"
+            ));
+        }
+        _ => value.into(),
     };
 
     Ok(KeyVal {
+        side: None,
         key_val_type,
         key: key.to_string(),
-        value: value.to_string(),
+        value: Some(value),
+    })
+}
+
+fn parse_key(key: &str) -> Result<(KeyValType, &str)> {
+    match key.chars().next() {
+        Some('@') => Ok((KeyValType::Body, &key[1..])),
+        Some('%') => Ok((KeyValType::Header, &key[1..])),
+        Some('$') => Ok((KeyValType::Var, &key[1..])),
+        Some(k) if k.is_alphabetic() => Ok((KeyValType::Query, key)),
+        _ => Err(anyhow!("Invalid key value pair")),
+    }
+}
+
+/// Split `@key@path` into the key and the file, where the key has no `=`.
+fn parse_file(s: &str) -> Option<(&str, &str)> {
+    let (key, file) = s.strip_prefix('@')?.split_once('@')?;
+    match key.trim() {
+        key if key.is_empty() || key.contains('=') => None,
+        key => Some((key, file.trim())),
+    }
+}
+
+/// Parse `@key-` or `%key-`, which remove a body field or a header.
+fn parse_removal(s: &str) -> Option<(KeyValType, &str)> {
+    if s.contains('=') {
+        return None;
+    }
+    let key = s.trim().strip_suffix('-')?;
+    match parse_key(key).ok()? {
+        (KeyValType::Body, key) if key.parse::<JsonPath>().is_ok() => Some((KeyValType::Body, key)),
+        (KeyValType::Header, key) if !key.is_empty() => Some((KeyValType::Header, key)),
+        _ => None,
+    }
+}
+
+/// Get the value of a body field, read from a file with `@path`
+/// and parsed as JSON if it is `typed`. A leading `@@` is a literal `@`.
+fn body_value(value: &str, typed: bool) -> Result<Value> {
+    let text = match value.strip_prefix('@') {
+        Some(literal) if literal.starts_with('@') => literal.to_string(),
+        Some(path) => std::fs::read_to_string(path.trim())
+            .map_err(|e| anyhow!("failed to read {}: {}", path.trim(), e))?,
+        None => value.to_string(),
+    };
+    if typed {
+        serde_json::from_str(&text).map_err(|e| anyhow!("invalid JSON value `{}`: {}", value, e))
+    } else {
+        Ok(text.into())
+    }
+}
+
+/// Text of a value which isn't a body field, these are always strings.
+fn text(value: impl Into<Option<Value>>) -> String {
+    match value.into() {
+        Some(Value::String(text)) => text,
+        Some(value) => value.to_string(),
+        None => String::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: String,
+}
+
+/// Parse the report target like `junit=report.xml` from the command line arguments.
+pub fn parse_report(s: &str) -> Result<ReportTarget> {
+    let (format, path) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid report, expected format=path"))?;
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(anyhow!("Invalid report, expected format=path"));
+    }
+
+    Ok(ReportTarget {
+        format: format.trim().parse()?,
+        path: path.to_string(),
     })
 }
 
 impl From<Vec<KeyVal>> for ExtraArgs {
     fn from(key_vals: Vec<KeyVal>) -> Self {
-        let mut query = Vec::new();
-        let mut header = Vec::new();
-        let mut body = Vec::new();
+        let mut shared = SideArgs::default();
+        let mut req1 = SideArgs::default();
+        let mut req2 = SideArgs::default();
+        let mut vars = Vec::new();
 
         for key_val in key_vals {
+            let args = match key_val.side {
+                None => &mut shared,
+                Some(Side::Req1) => &mut req1,
+                Some(Side::Req2) => &mut req2,
+            };
             match key_val.key_val_type {
-                KeyValType::Query => query.push((key_val.key, key_val.value)),
-                KeyValType::Header => header.push((key_val.key, key_val.value)),
-                KeyValType::Body => body.push((key_val.key, key_val.value)),
+                KeyValType::Query => args.query.push((key_val.key, text(key_val.value))),
+                KeyValType::Header => args.header.push((key_val.key, key_val.value.map(text))),
+                KeyValType::Body => args.body.push((key_val.key, key_val.value)),
+                KeyValType::File => args.files.push((key_val.key, text(key_val.value))),
+                // Rejected with a side when parsed.
+                KeyValType::Var => vars.push((key_val.key, text(key_val.value))),
             }
         }
 
         ExtraArgs {
-            query,
-            header,
-            body,
+            query: shared.query,
+            header: shared.header,
+            body: shared.body,
+            files: shared.files,
+            vars,
+            req1,
+            req2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key_val(key_val_type: KeyValType, key: &str, value: Option<Value>) -> KeyVal {
+        KeyVal {
+            side: None,
+            key_val_type,
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn parse_key_val_reads_each_kind_of_override() {
+        use KeyValType::*;
+        let cases = [
+            ("a=1", key_val(Query, "a", Some(json!("1")))),
+            ("%x-a = 1", key_val(Header, "x-a", Some(json!("1")))),
+            ("$name=value", key_val(Var, "name", Some(json!("value")))),
+            ("@a.b=1", key_val(Body, "a.b", Some(json!("1")))),
+            ("@qty:=1", key_val(Body, "qty", Some(json!(1)))),
+            ("@tags:=[\"a\"]", key_val(Body, "tags", Some(json!(["a"])))),
+            (
+                "@items[1].qty:=null",
+                key_val(Body, "items[1].qty", Some(json!(null))),
+            ),
+            (
+                "@handle=@@rustlang",
+                key_val(Body, "handle", Some(json!("@rustlang"))),
+            ),
+            (
+                "@handle:=\"@@\"",
+                key_val(Body, "handle", Some(json!("@@"))),
+            ),
+            ("@a.b-", key_val(Body, "a.b", None)),
+            ("%authorization-", key_val(Header, "authorization", None)),
+            (
+                "@avatar@./a.png;type=image/png",
+                key_val(File, "avatar", Some(json!("./a.png;type=image/png"))),
+            ),
+            (
+                "2:%authorization=none",
+                KeyVal {
+                    side: Some(Side::Req2),
+                    ..key_val(Header, "authorization", Some(json!("none")))
+                },
+            ),
+            (
+                "1:@a-",
+                KeyVal {
+                    side: Some(Side::Req1),
+                    ..key_val(Body, "a", None)
+                },
+            ),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(parse_key_val(arg).unwrap(), expected, "parse `{}`", arg);
+        }
+    }
+
+    #[test]
+    fn parse_key_val_reports_invalid_overrides() {
+        let cases = [
+            ("a", "Invalid key value pair"),
+            ("=1", "Invalid key value pair"),
+            ("a-", "Invalid key value pair"),
+            (
+                "%a:=1",
+                "Typed values with `:=` are only supported for body fields",
+            ),
+            (
+                "1:$name=value",
+                "Template variables apply to the whole config and can't have a `1:` or `2:` prefix",
+            ),
+            ("@a[=1", "invalid path `a[`: missing `]`"),
+            (
+                "@a@;type=text/plain",
+                "missing file path in `;type=text/plain`",
+            ),
+            (
+                "@a@./a.png;size=1",
+                "invalid file option `size=1`, expected `type=` or `filename=`",
+            ),
+        ];
+        for (arg, expected) in cases {
+            let err = parse_key_val(arg).unwrap_err();
+            assert_eq!(err.to_string(), expected, "parse `{}`", arg);
+        }
+
+        let err = parse_key_val("@qty:=one").unwrap_err().to_string();
+        assert!(err.starts_with("invalid JSON value `one`: "), "{}", err);
+        let err = parse_key_val("@a=@/nonexistent/rdiff")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("failed to read /nonexistent/rdiff: "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_removal_only_removes_body_fields_and_headers() {
+        let cases = [
+            ("@a.b-", Some((KeyValType::Body, "a.b"))),
+            ("@items[0]-", Some((KeyValType::Body, "items[0]"))),
+            ("%x-a-", Some((KeyValType::Header, "x-a"))),
+            ("a-", None),
+            ("$a-", None),
+            ("%-", None),
+            ("@-", None),
+            ("@a-=1", None),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(parse_removal(arg), expected, "parse `{}`", arg);
+        }
+    }
+
+    #[test]
+    fn body_value_reads_files_unless_escaped() {
+        let path = std::env::temp_dir().join(format!("rdiff-body-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"a": [1]}"#).unwrap();
+        let file = format!("@{}", path.display());
+
+        let cases = [
+            ("1", false, json!("1")),
+            ("1", true, json!(1)),
+            ("@@a", false, json!("@a")),
+            ("\"@@a\"", true, json!("@@a")),
+            (file.as_str(), false, json!(r#"{"a": [1]}"#)),
+            (file.as_str(), true, json!({"a": [1]})),
+        ];
+        for (value, typed, expected) in cases {
+            assert_eq!(
+                body_value(value, typed).unwrap(),
+                expected,
+                "value `{}`",
+                value
+            );
         }
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -0,0 +1,277 @@
+use anyhow::{Context, Result, anyhow};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use reqwest::Client;
+use reqwest::header::HeaderValue;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::interpolate::{MASK, add_secret};
+use super::{Issues, Validateable};
+
+// Tokens are fetched once per distinct OAuth2 config and shared by every request,
+// until they expire. Each config has its own lock, held while fetching, so concurrent
+// requests wait for the same token instead of each fetching one, without waiting
+// for the tokens of other configs.
+static TOKENS: LazyLock<Mutex<HashMap<OAuth2, TokenSlot>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+type TokenSlot = Arc<tokio::sync::Mutex<Option<Token>>>;
+
+/// Tokens are refreshed this long before they expire, so they don't expire in flight.
+/// Short-lived tokens are refreshed after half their lifetime instead.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+/// Timeout of a token request, so a stuck token endpoint doesn't hold up every request.
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a request authenticates, sets the `authorization` header unless it is set already.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Auth {
+    /// HTTP basic authentication with a user name and password.
+    Basic {
+        user: String,
+        #[serde(skip_serializing_if = "String::is_empty", default)]
+        password: String,
+    },
+    /// A bearer token, given as is, or read from an environment variable or a file
+    /// when the request is sent. Exactly one of them must be set.
+    Bearer {
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        token: Option<String>,
+        /// Environment variable with the token.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        token_env: Option<String>,
+        /// File with the token, relative to the config file.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        token_file: Option<String>,
+    },
+    /// A bearer token fetched from an OAuth2 token endpoint, cached until it expires.
+    Oauth2(OAuth2),
+}
+
+/// Settings of an OAuth2 token request.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2 {
+    /// URL of the token endpoint.
+    pub token_url: Url,
+    /// Defaults to `client_credentials`.
+    #[serde(skip_serializing_if = "is_default_grant", default)]
+    pub grant: Grant,
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub client_secret: Option<String>,
+    /// User name of the `password` grant.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub username: Option<String>,
+    /// Password of the `password` grant.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub password: Option<String>,
+    /// Space separated scopes to request.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Grant {
+    #[default]
+    ClientCredentials,
+    Password,
+}
+
+/// A fetched token and when to fetch a new one.
+#[derive(Debug, Clone)]
+struct Token {
+    header: HeaderValue,
+    refresh_at: Option<Instant>,
+}
+
+/// Response of a token endpoint.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+fn is_default_grant(grant: &Grant) -> bool {
+    *grant == Grant::default()
+}
+
+impl Auth {
+    /// Get the value of the `authorization` header, fetching a token if needed.
+    pub async fn header(&self, client: &Client) -> Result<HeaderValue> {
+        match self {
+            Auth::Basic { user, password } => {
+                let credentials = BASE64_STANDARD.encode(format!("{}:{}", user, password));
+                secret_header(format!("Basic {}", credentials))
+            }
+            Auth::Bearer {
+                token,
+                token_env,
+                token_file,
+            } => {
+                let token = match (token, token_env, token_file) {
+                    (Some(token), ..) => token.clone(),
+                    (_, Some(name), _) => std::env::var(name)
+                        .map_err(|_| anyhow!("environment variable `{}` is not set", name))?,
+                    (.., Some(path)) => tokio::fs::read_to_string(path)
+                        .await
+                        .with_context(|| format!("failed to read token file {}", path))?
+                        .trim()
+                        .to_string(),
+                    _ => return Err(anyhow!("bearer auth needs a `token`")),
+                };
+                add_secret(&token);
+                secret_header(format!("Bearer {}", token))
+            }
+            Auth::Oauth2(oauth2) => oauth2.header(client).await,
+        }
+    }
+
+    /// Get the `authorization` header with masked credentials, without reading or
+    /// fetching a token. OAuth2 tokens are assumed to be bearer tokens.
+    pub fn masked_header(&self) -> HeaderValue {
+        let scheme = match self {
+            Auth::Basic { .. } => "Basic",
+            Auth::Bearer { .. } | Auth::Oauth2(_) => "Bearer",
+        };
+        HeaderValue::from_str(&format!("{} {}", scheme, MASK))
+            .expect("a masked header is a valid header value")
+    }
+}
+
+impl OAuth2 {
+    /// Get the header of the cached token, or fetch a new one if it expired.
+    async fn header(&self, client: &Client) -> Result<HeaderValue> {
+        let slot = TOKENS
+            .lock()
+            .map_err(|_| anyhow!("token cache is poisoned"))?
+            .entry(self.clone())
+            .or_default()
+            .clone();
+        let mut token = slot.lock().await;
+        if let Some(token) = token.as_ref()
+            && token.refresh_at.is_none_or(|at| Instant::now() < at)
+        {
+            return Ok(token.header.clone());
+        }
+
+        let fetched = self
+            .fetch(client)
+            .await
+            .with_context(|| format!("failed to fetch OAuth2 token from {}", self.token_url))?;
+        Ok(token.insert(fetched).header.clone())
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<Token> {
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+        match self.grant {
+            Grant::ClientCredentials => form.push(("grant_type", "client_credentials")),
+            Grant::Password => {
+                form.push(("grant_type", "password"));
+                form.push(("username", self.username.as_deref().unwrap_or_default()));
+                form.push(("password", self.password.as_deref().unwrap_or_default()));
+            }
+        }
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+
+        let fetched_at = Instant::now();
+        let resp = client
+            .post(self.token_url.clone())
+            .form(&form)
+            .timeout(TOKEN_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        let token: TokenResponse =
+            serde_json::from_str(&resp.text().await?).context("invalid token response")?;
+
+        add_secret(&token.access_token);
+        let token_type = match token.token_type {
+            Some(t) if !t.eq_ignore_ascii_case("bearer") => t,
+            _ => "Bearer".to_string(),
+        };
+        let refresh_at = token.expires_in.map(|secs| {
+            let lifetime = Duration::from_secs(secs);
+            fetched_at + lifetime - EXPIRY_MARGIN.min(lifetime / 2)
+        });
+        Ok(Token {
+            header: secret_header(format!("{} {}", token_type, token.access_token))?,
+            refresh_at,
+        })
+    }
+}
+
+/// A header value which is hidden from debug output.
+fn secret_header(value: String) -> Result<HeaderValue> {
+    let mut header = HeaderValue::try_from(value)
+        .map_err(|_| anyhow!("credentials must not contain control characters"))?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+impl Validateable for Auth {
+    fn check(&self, issues: &mut Issues) {
+        match self {
+            Auth::Basic { user, .. } => {
+                if user.contains(':') {
+                    issues.at("user", |issues| issues.error("user must not contain `:`"));
+                }
+            }
+            Auth::Bearer {
+                token,
+                token_env,
+                token_file,
+            } => {
+                let sources = [token, token_env, token_file];
+                if sources.iter().filter(|s| s.is_some()).count() != 1 {
+                    issues
+                        .error("exactly one of `token`, `token_env` and `token_file` must be set");
+                }
+                if let Some(path) = token_file
+                    && !Path::new(path).is_file()
+                {
+                    issues.at("token_file", |issues| {
+                        issues.warning(format!("file `{}` not found", path))
+                    });
+                }
+            }
+            Auth::Oauth2(oauth2) => {
+                if !matches!(oauth2.token_url.scheme(), "http" | "https") {
+                    issues.at("token_url", |issues| {
+                        issues.error(format!(
+                            "URL scheme must be http or https but got `{}`",
+                            oauth2.token_url.scheme()
+                        ))
+                    });
+                }
+                if oauth2.grant == Grant::Password {
+                    for (key, value) in [
+                        ("username", &oauth2.username),
+                        ("password", &oauth2.password),
+                    ] {
+                        if value.is_none() {
+                            issues.at(key, |issues| {
+                                issues.error("required by the `password` grant")
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
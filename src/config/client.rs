@@ -0,0 +1,131 @@
+use anyhow::{Result, anyhow};
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use super::duration;
+
+// Clients are built once per distinct config and shared, so connections
+// and TLS sessions are reused across requests.
+static CLIENTS: LazyLock<Mutex<HashMap<ClientConfig, Client>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Connect timeout unless `connect_timeout` is set.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout of the whole request unless `timeout` is set.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP client settings. Could be set for the whole config and per profile,
+/// unset fields fall back to the outer config.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    /// Timeout for establishing a connection, e.g. `5s` or `500ms`. Defaults to `10s`,
+    /// `null` or `0` disables it like the other timeouts.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for each read of the response.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub read_timeout: Option<Duration>,
+    /// Timeout for the whole request, from connecting until the body is read.
+    /// Defaults to `30s`.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub timeout: Option<Duration>,
+    /// How long idle connections are kept in the pool, forever if disabled.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub pool_idle_timeout: Option<Duration>,
+    /// Maximum idle connections kept per host.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub http_version: Option<HttpVersion>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub user_agent: Option<String>,
+    /// Send `accept-encoding: gzip` and decompress responses.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub gzip: Option<bool>,
+    /// Send `accept-encoding: br` and decompress responses.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub brotli: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// Only use HTTP/1.
+    Http1,
+    /// Use HTTP/2 with prior knowledge, without upgrading from HTTP/1.
+    Http2,
+}
+
+impl ClientConfig {
+    /// Merge with a more specific config, whose set fields take precedence.
+    pub fn merge(&self, other: &ClientConfig) -> ClientConfig {
+        ClientConfig {
+            connect_timeout: other.connect_timeout.or(self.connect_timeout),
+            read_timeout: other.read_timeout.or(self.read_timeout),
+            timeout: other.timeout.or(self.timeout),
+            pool_idle_timeout: other.pool_idle_timeout.or(self.pool_idle_timeout),
+            pool_max_idle_per_host: other.pool_max_idle_per_host.or(self.pool_max_idle_per_host),
+            http_version: other.http_version.or(self.http_version),
+            user_agent: other.user_agent.clone().or_else(|| self.user_agent.clone()),
+            gzip: other.gzip.or(self.gzip),
+            brotli: other.brotli.or(self.brotli),
+        }
+    }
+
+    /// Get the shared client for this config, building it on first use.
+    pub fn client(&self) -> Result<Client> {
+        let mut clients = CLIENTS
+            .lock()
+            .map_err(|_| anyhow!("client cache is poisoned"))?;
+        if let Some(client) = clients.get(self) {
+            return Ok(client.clone());
+        }
+
+        let client = self.build()?;
+        clients.insert(self.clone(), client.clone());
+        Ok(client)
+    }
+
+    fn build(&self) -> Result<Client> {
+        // A zero timeout, e.g. from `null`, disables it.
+        let enabled = |timeout: Duration| (!timeout.is_zero()).then_some(timeout);
+        let mut builder = Client::builder();
+        if let Some(timeout) = enabled(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)) {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = enabled(self.timeout.unwrap_or(DEFAULT_TIMEOUT)) {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout.and_then(enabled) {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(enabled(timeout));
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        match self.http_version {
+            Some(HttpVersion::Http1) => builder = builder.http1_only(),
+            Some(HttpVersion::Http2) => builder = builder.http2_prior_knowledge(),
+            None => {}
+        }
+        if let Some(ref user_agent) = self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        builder = builder
+            .gzip(self.gzip.unwrap_or_default())
+            .brotli(self.brotli.unwrap_or_default());
+
+        Ok(builder.build()?)
+    }
+}
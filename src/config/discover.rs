@@ -0,0 +1,165 @@
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::ConfigFormat;
+
+/// Directory below `$XDG_CONFIG_HOME` holding user-wide configs.
+const XDG_DIR: &str = "rdiff";
+
+/// A config file found by `discover_config` and why it was chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub source: ConfigSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Given with `--config`.
+    Flag,
+    /// Given by an environment variable.
+    Env(String),
+    /// Found in the current directory or a parent up to the repository root.
+    Ancestor,
+    /// Found in `$XDG_CONFIG_HOME/rdiff/`.
+    Xdg,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Flag => write!(f, "given with --config"),
+            ConfigSource::Env(var) => write!(f, "given by the {} environment variable", var),
+            ConfigSource::Ancestor => write!(
+                f,
+                "found in the current directory or a parent up to the repository root"
+            ),
+            ConfigSource::Xdg => write!(f, "found in the user config directory"),
+        }
+    }
+}
+
+/// Find the config file: `flag` if given, then the path in `env_var`, then `file_name`
+/// in the current directory and its parents up to the repository root,
+/// then `file_name` in `$XDG_CONFIG_HOME/rdiff/`.
+/// In each directory `file_name` is searched with every extension of
+/// `ConfigFormat::EXTENSIONS` in turn, so `rdiff.yaml` comes before `rdiff.json`.
+pub fn discover_config(
+    flag: Option<&str>,
+    env_var: &str,
+    file_name: &str,
+) -> Result<ConfigLocation> {
+    if let Some(path) = flag {
+        return Ok(ConfigLocation {
+            path: path.into(),
+            source: ConfigSource::Flag,
+        });
+    }
+    if let Some(path) = std::env::var_os(env_var).filter(|p| !p.is_empty()) {
+        return Ok(ConfigLocation {
+            path: path.into(),
+            source: ConfigSource::Env(env_var.to_string()),
+        });
+    }
+
+    let mut searched = Vec::new();
+    let cwd = std::env::current_dir()?;
+    for dir in search_dirs(&cwd) {
+        if let Some(path) = find_file(dir, file_name) {
+            return Ok(ConfigLocation {
+                path,
+                source: ConfigSource::Ancestor,
+            });
+        }
+        searched.push(dir.to_path_buf());
+    }
+    if let Some(dir) = xdg_config_home() {
+        let dir = dir.join(XDG_DIR);
+        if let Some(path) = find_file(&dir, file_name) {
+            return Ok(ConfigLocation {
+                path,
+                source: ConfigSource::Xdg,
+            });
+        }
+        searched.push(dir);
+    }
+
+    let names =
+        Path::new(file_name).with_extension(format!("{{{}}}", ConfigFormat::EXTENSIONS.join(",")));
+    let searched: Vec<String> = searched
+        .iter()
+        .map(|dir| dir.join(&names).display().to_string())
+        .collect();
+    Err(anyhow!(
+        "no config file found, use --config or {}. Searched:\n  {}",
+        env_var,
+        searched.join("\n  ")
+    ))
+}
+
+/// Find `file_name` with the first extension it exists with in `dir`.
+fn find_file(dir: &Path, file_name: &str) -> Option<PathBuf> {
+    ConfigFormat::EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(file_name).with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+/// The current directory and its parents up to the repository root.
+/// Outside a repository only the current directory is searched.
+fn search_dirs(cwd: &Path) -> Vec<&Path> {
+    let ancestors: Vec<&Path> = cwd.ancestors().collect();
+    match ancestors.iter().position(|dir| dir.join(".git").exists()) {
+        Some(root) => ancestors[..=root].to_vec(),
+        None => vec![cwd],
+    }
+}
+
+fn xdg_config_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME").filter(|p| !p.is_empty()) {
+        Some(dir) => Some(dir.into()),
+        None => std::env::home_dir().map(|home| home.join(".config")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Find `file_name` like `discover_config` does from `cwd`, without the flag,
+    /// environment and user config directory.
+    fn find_from(cwd: &Path, file_name: &str) -> Option<PathBuf> {
+        search_dirs(cwd)
+            .into_iter()
+            .find_map(|dir| find_file(dir, file_name))
+    }
+
+    #[test]
+    fn configs_are_found_up_to_the_repository_root() {
+        let root = std::env::temp_dir().join(format!("rdiff-discover-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let repo = root.join("repo");
+        let cwd = repo.join("a/b");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&cwd).unwrap();
+        for file in [
+            "rdiff.toml",
+            "repo/xreq.json",
+            "repo/a/rdiff.json",
+            "repo/a/rdiff.yaml",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(search_dirs(&cwd), [cwd.as_path(), &repo.join("a"), &repo]);
+        assert_eq!(find_from(&cwd, "rdiff"), Some(repo.join("a/rdiff.yaml")));
+        assert_eq!(find_from(&cwd, "xreq"), Some(repo.join("xreq.json")));
+        // The repository root is the last directory searched.
+        assert_eq!(find_from(&cwd, "other"), None);
+        assert_eq!(find_from(&repo, "rdiff"), None);
+        // Outside a repository only the current directory is searched.
+        assert_eq!(search_dirs(&root), [root.as_path()]);
+        assert_eq!(find_from(&root, "rdiff"), Some(root.join("rdiff.toml")));
+    }
+}
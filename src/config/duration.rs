@@ -0,0 +1,155 @@
+//! (De)serialize optional durations as `30s`, `500ms`, `2m` or a number of seconds.
+//! `null` is a zero duration, which disables a timeout. Durations are whole milliseconds.
+use schemars::{Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serializer, de::Error};
+use std::time::Duration;
+
+pub fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) if d.subsec_millis() == 0 => s.serialize_str(&format!("{}s", d.as_secs())),
+        Some(d) => s.serialize_str(&format!("{}ms", d.as_millis())),
+        None => s.serialize_none(),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Secs(f64),
+        Text(String),
+    }
+
+    let millis = match Option::<Raw>::deserialize(d)? {
+        None => 0.0,
+        Some(Raw::Secs(secs)) => secs * 1000.0,
+        Some(Raw::Text(text)) => parse_millis(&text).ok_or_else(|| {
+            Error::custom(format!(
+                "invalid duration `{}`, expected e.g. `30s`, `500ms` or `2m`",
+                text
+            ))
+        })?,
+    };
+    from_millis(millis).map(Some).map_err(Error::custom)
+}
+
+/// Parse a duration in milliseconds, as precise as the text.
+fn parse_millis(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let idx = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let value: f64 = text[..idx].parse().ok()?;
+    match text[idx..].trim() {
+        "ms" => Some(value),
+        "" | "s" => Some(value * 1000.0),
+        "m" => Some(value * 60_000.0),
+        "h" => Some(value * 3_600_000.0),
+        _ => None,
+    }
+}
+
+/// Sub-millisecond durations are rejected rather than rounded, as they are
+/// most likely a mistaken unit.
+fn from_millis(millis: f64) -> Result<Duration, String> {
+    let rounded = millis.round();
+    if (millis - rounded).abs() > 1e-6 {
+        return Err(format!(
+            "duration of {}ms is not a whole number of milliseconds",
+            millis
+        ));
+    }
+    if !(0.0..=u64::MAX as f64).contains(&rounded) {
+        return Err(format!("duration of {}ms is out of range", millis));
+    }
+    Ok(Duration::from_millis(rounded as u64))
+}
+
+/// JSON Schema of a duration, a number of seconds or a string like `30s`,
+/// or a string with `${VAR}` or `{{var}}` which is resolved on load.
+pub fn schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            { "type": "null" },
+            { "type": "number", "minimum": 0 },
+            {
+                "type": "string",
+                "pattern": "^(\\s*[0-9.]+\\s*(ms|s|m|h)?\\s*|.*(\\$\\{|\\{\\{).*)$",
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        #[serde(with = "super", default)]
+        timeout: Option<Duration>,
+    }
+
+    fn parse(yaml: &str) -> Result<Option<Duration>, String> {
+        serde_yaml::from_str::<Config>(yaml)
+            .map(|c| c.timeout)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn deserialize_reads_units_and_seconds() {
+        let ms = Duration::from_millis;
+        let cases = [
+            ("{}", None),
+            ("timeout: null", Some(Duration::ZERO)),
+            ("timeout: 0", Some(Duration::ZERO)),
+            ("timeout: 30s", Some(ms(30_000))),
+            ("timeout: ' 500 ms '", Some(ms(500))),
+            ("timeout: 2m", Some(ms(120_000))),
+            ("timeout: 1h", Some(ms(3_600_000))),
+            ("timeout: 0.3s", Some(ms(300))),
+            ("timeout: '1.5'", Some(ms(1500))),
+            ("timeout: 1.5", Some(ms(1500))),
+            ("timeout: 0.001", Some(ms(1))),
+        ];
+        for (yaml, expected) in cases {
+            assert_eq!(parse(yaml), Ok(expected), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn deserialize_rejects_invalid_and_sub_millisecond_durations() {
+        let cases = [
+            ("timeout: 500us", "invalid duration `500us`"),
+            ("timeout: 1x", "invalid duration `1x`"),
+            ("timeout: -1s", "invalid duration `-1s`"),
+            ("timeout: ''", "invalid duration ``"),
+            (
+                "timeout: 1.5ms",
+                "duration of 1.5ms is not a whole number of milliseconds",
+            ),
+            (
+                "timeout: 0.0005",
+                "duration of 0.5ms is not a whole number of milliseconds",
+            ),
+            ("timeout: -1", "duration of -1000ms is out of range"),
+        ];
+        for (yaml, expected) in cases {
+            let err = parse(yaml).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", yaml, err);
+        }
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        for (millis, text) in [(0, "0s"), (1500, "1500ms"), (30_000, "30s")] {
+            let config = Config {
+                timeout: Some(Duration::from_millis(millis)),
+            };
+            let yaml = serde_yaml::to_string(&config).unwrap();
+            assert_eq!(yaml, format!("timeout: {}\n", text));
+            assert_eq!(parse(&yaml), Ok(config.timeout));
+        }
+    }
+}
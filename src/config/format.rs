@@ -0,0 +1,138 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::path::Path;
+use std::str::FromStr;
+
+/// File format of a config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            _ => Err(anyhow!("Unsupported config format: {}", s)),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Extensions of config files, in the order they are searched by `discover_config`.
+    pub const EXTENSIONS: [&'static str; 4] = ["yaml", "yml", "json", "toml"];
+
+    /// Detect the format from the extension of a file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    /// Parse a config document.
+    pub fn parse(&self, content: &str) -> Result<Value> {
+        match self {
+            ConfigFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+            ConfigFormat::Json => Ok(serde_json::from_str(content)?),
+            ConfigFormat::Toml => Ok(toml::from_str(content)?),
+        }
+    }
+
+    /// Render a config in this format.
+    pub fn render<T: Serialize>(&self, config: &T) -> Result<String> {
+        match self {
+            ConfigFormat::Yaml => Ok(serde_yaml::to_string(config)?),
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(config)?),
+            ConfigFormat::Toml => Ok(toml::to_string(config)?),
+        }
+    }
+
+    /// Name of the syntax used to highlight a config in this format.
+    pub fn syntax(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffConfig, LoadConfig, RequestConfig};
+
+    const FORMATS: [ConfigFormat; 3] = [ConfigFormat::Yaml, ConfigFormat::Json, ConfigFormat::Toml];
+
+    /// Render `config` in every format and parse it back, checking nothing was lost.
+    fn assert_round_trips<T: LoadConfig + Serialize>(config: &str) {
+        let expected = ConfigFormat::Yaml
+            .render(&T::from_yaml(config).unwrap())
+            .unwrap();
+        for format in FORMATS {
+            let rendered = format.render(&T::from_yaml(config).unwrap()).unwrap();
+            let parsed = T::parse(&rendered, format, &[]).unwrap();
+            let actual = ConfigFormat::Yaml.render(&parsed).unwrap();
+            assert_eq!(actual, expected, "{:?}:\n{}", format, rendered);
+        }
+    }
+
+    #[test]
+    fn configs_round_trip_through_every_format() {
+        assert_round_trips::<DiffConfig>(
+            r#"
+client: {timeout: 10s}
+users:
+  tags: [smoke]
+  req1:
+    method: POST
+    url: "https://a.test/users?page=1"
+    params: {limit: 10, filter: {active: true}}
+    headers: {x-team: core}
+    auth: {type: bearer, token: t}
+    body: {name: alice, roles: [admin], age: 30}
+  req2: {url: "https://b.test/users", retry: {max_attempts: 3}}
+  resp:
+    skip_headers: [date]
+    skip_body: ["items[*].id"]
+    normalize: [{body: [updated_at], action: round, digits: 2}]
+    diff_mode: json
+"#,
+        );
+        assert_round_trips::<RequestConfig>(
+            r#"
+users:
+  url: "https://a.test/users"
+  headers: {accept: application/json}
+  body_raw: "<user/>"
+  client: {connect_timeout: 1s, timeout: null}
+"#,
+        );
+    }
+
+    #[test]
+    fn formats_are_detected_from_extensions() {
+        let cases = [
+            ("rdiff.yaml", Some(ConfigFormat::Yaml)),
+            ("a/rdiff.yml", Some(ConfigFormat::Yaml)),
+            ("rdiff.json", Some(ConfigFormat::Json)),
+            ("rdiff.toml", Some(ConfigFormat::Toml)),
+            ("rdiff.ini", None),
+            ("rdiff", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                ConfigFormat::from_path(Path::new(path)),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+}
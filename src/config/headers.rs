@@ -0,0 +1,63 @@
+//! (De)serialize a header map, naming the offending header when a name or value is invalid.
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::de::{DeserializeSeed, Error, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt;
+
+pub fn serialize<S: Serializer>(headers: &HeaderMap, s: S) -> Result<S::Ok, S::Error> {
+    http_serde::header_map::serialize(headers, s)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<HeaderMap, D::Error> {
+    d.deserialize_map(HeaderMapVisitor)
+}
+
+struct HeaderMapVisitor;
+
+impl<'de> Visitor<'de> for HeaderMapVisitor {
+    type Value = HeaderMap;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a mapping of header names to values")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<HeaderMap, M::Error> {
+        let mut headers = HeaderMap::new();
+        while let Some(name) = map.next_key::<String>()? {
+            // The name is checked with the value, so errors are reported at the header.
+            let (key, value) = map.next_value_seed(Header(&name))?;
+            // `null` removes a header inherited with `extends`, nothing is left to remove here.
+            if let Some(value) = value {
+                headers.append(key, value);
+            }
+        }
+        Ok(headers)
+    }
+}
+
+/// Reads the value of the header with this name.
+struct Header<'a>(&'a str);
+
+impl<'de> DeserializeSeed<'de> for Header<'_> {
+    type Value = (HeaderName, Option<HeaderValue>);
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        let name = self.0;
+        let key = HeaderName::try_from(name).map_err(|_| {
+            D::Error::custom(format!(
+                "invalid header name `{}`, only letters, digits and !#$%&'*+-.^_`|~ are allowed",
+                name
+            ))
+        })?;
+        let Some(value) = Option::<String>::deserialize(d)? else {
+            return Ok((key, None));
+        };
+        let value = HeaderValue::try_from(&value).map_err(|_| {
+            D::Error::custom(format!(
+                "invalid value of header `{}`, control characters and line breaks are not allowed",
+                name
+            ))
+        })?;
+        Ok((key, Some(value)))
+    }
+}
@@ -0,0 +1,347 @@
+use anyhow::{Context, Result, anyhow};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::inherit::merge;
+use super::issues::{Issues, Location};
+use super::source_map::SourceMap;
+use super::{ConfigFormat, RESERVED_KEYS};
+
+const INCLUDE: &str = "include";
+/// Keys of file paths, which are relative to the config file they are written in.
+const PATH_KEYS: [&str; 3] = ["body_file", "token_file", "@file"];
+/// Start of a `${file:path}` secret, whose path is also relative to the config file.
+const FILE_SECRET: &str = "${file:";
+
+/// Read a config file, or every config file of a directory, following `include` globs.
+/// The format of a file is `format` if given, otherwise detected from its extension.
+/// Files in directories and included files are always detected.
+/// Also returns where each value was read from, and profiles defined in several files.
+pub fn read_config(
+    path: &Path,
+    format: Option<ConfigFormat>,
+) -> Result<(Value, SourceMap, Issues)> {
+    let mut sources = Sources::default();
+    match format {
+        Some(format) if !path.is_dir() => sources.add_file(path, format)?,
+        _ => sources.add_path(path)?,
+    }
+    Ok(sources.into_parts())
+}
+
+/// Parse a config and follow its `include` globs, relative to `dir`.
+pub fn include_config(
+    content: &str,
+    format: ConfigFormat,
+    dir: &Path,
+) -> Result<(Value, SourceMap, Issues)> {
+    let mut sources = Sources::default();
+    sources.add_content(content, format, dir, "<input>")?;
+    Ok(sources.into_parts())
+}
+
+/// Profiles and settings collected from config files.
+#[derive(Debug, Default)]
+struct Sources {
+    config: Mapping,
+    /// Where each profile was read from, a location or a file without locations.
+    origins: HashMap<String, String>,
+    /// Files already read, so each one is only included once.
+    visited: HashSet<PathBuf>,
+    locations: SourceMap,
+    issues: Issues,
+}
+
+impl Sources {
+    fn into_parts(self) -> (Value, SourceMap, Issues) {
+        (Value::Mapping(self.config), self.locations, self.issues)
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        if !path.is_dir() {
+            let format = ConfigFormat::from_path(path).unwrap_or_default();
+            return self.add_file(path, format);
+        }
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)
+            .with_context(|| format!("failed to read config directory {}", path.display()))?
+        {
+            let file = entry?.path();
+            if file.is_file()
+                && let Some(format) = ConfigFormat::from_path(&file)
+            {
+                files.push((file, format));
+            }
+        }
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        files
+            .iter()
+            .try_for_each(|(file, format)| self.add_file(file, *format))
+    }
+
+    fn add_file(&mut self, path: &Path, format: ConfigFormat) -> Result<()> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        if !self.visited.insert(canonical) {
+            return Ok(());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        self.add_content(&content, format, dir, &path.display().to_string())
+    }
+
+    fn add_content(
+        &mut self,
+        content: &str,
+        format: ConfigFormat,
+        dir: &Path,
+        origin: &str,
+    ) -> Result<()> {
+        let value = format
+            .parse(content)
+            .with_context(|| format!("failed to parse config {}", origin))?;
+        // JSON is YAML too, TOML files have no locations.
+        let mut locations = SourceMap::default();
+        if format != ConfigFormat::Toml {
+            locations.add(content, origin);
+        }
+        self.locations.extend(&locations);
+        self.add_value(value, dir, origin, &locations)
+    }
+
+    /// Add the profiles and settings of a file whose values are at `locations`.
+    fn add_value(
+        &mut self,
+        value: Value,
+        dir: &Path,
+        origin: &str,
+        locations: &SourceMap,
+    ) -> Result<()> {
+        let mut mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => Mapping::new(),
+            _ => return Err(anyhow!("config {} must be a mapping", origin)),
+        };
+        let patterns = match mapping.remove(INCLUDE) {
+            Some(Value::String(pattern)) => vec![pattern],
+            Some(Value::Sequence(patterns)) => patterns
+                .into_iter()
+                .map(|p| match p {
+                    Value::String(pattern) => Ok(pattern),
+                    _ => Err(anyhow!("`{}` of {} must be glob patterns", INCLUDE, origin)),
+                })
+                .collect::<Result<_>>()?,
+            Some(Value::Null) | None => vec![],
+            Some(_) => return Err(anyhow!("`{}` of {} must be glob patterns", INCLUDE, origin)),
+        };
+
+        for (key, mut value) in mapping {
+            resolve_paths(&mut value, dir);
+            let name = key.as_str().unwrap_or_default().to_string();
+            if RESERVED_KEYS.contains(&name.as_str()) {
+                match (self.config.get_mut(&key), value) {
+                    (Some(Value::Mapping(settings)), Value::Mapping(value)) => {
+                        merge(settings, value)
+                    }
+                    (_, value) => {
+                        self.config.insert(key, value);
+                    }
+                }
+                continue;
+            }
+            let location = locations.locate(&name).cloned();
+            let defined = location
+                .as_ref()
+                .map_or(origin.to_string(), |l| l.to_string());
+            if let Some(first) = self.origins.get(&name) {
+                // The first definition is kept, so the others are still checked.
+                let location = location.unwrap_or_else(|| Location::file(origin));
+                let message = format!("duplicate profile, first defined at {}", first);
+                self.issues
+                    .at(&name, |issues| issues.error_at(location, message));
+                continue;
+            }
+            self.origins.insert(name, defined);
+            self.config.insert(key, value);
+        }
+
+        for pattern in patterns {
+            let full = dir.join(&pattern);
+            let mut paths = glob::glob(&full.to_string_lossy())
+                .with_context(|| format!("invalid include pattern `{}` in {}", pattern, origin))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if paths.is_empty() {
+                return Err(anyhow!(
+                    "include `{}` in {} matches no files",
+                    pattern,
+                    origin
+                ));
+            }
+            paths.sort();
+            paths.iter().try_for_each(|path| self.add_path(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Join relative file paths in `value` to `dir`. Paths starting with `${VAR}` or
+/// `{{var}}` are kept, as they are only known after interpolation.
+fn resolve_paths(value: &mut Value, dir: &Path) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                match value {
+                    Value::String(path)
+                        if key.as_str().is_some_and(|key| PATH_KEYS.contains(&key))
+                            && !path.starts_with(['$', '{'])
+                            && Path::new(path).is_relative() =>
+                    {
+                        *path = dir.join(&*path).display().to_string();
+                    }
+                    value => resolve_paths(value, dir),
+                }
+            }
+        }
+        Value::Sequence(seq) => seq.iter_mut().for_each(|value| resolve_paths(value, dir)),
+        Value::Tagged(tagged) => resolve_paths(&mut tagged.value, dir),
+        Value::String(s) if s.contains(FILE_SECRET) => *s = resolve_file_secrets(s, dir),
+        _ => {}
+    }
+}
+
+/// Join relative paths of `${file:path}` in a string to `dir`, except escaped `$${file:`.
+fn resolve_file_secrets(s: &str, dir: &Path) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find(FILE_SECRET) {
+        let escaped = rest[..idx].ends_with('$');
+        output.push_str(&rest[..idx + FILE_SECRET.len()]);
+        rest = &rest[idx + FILE_SECRET.len()..];
+        let end = rest.find('}').unwrap_or(rest.len());
+        let path = rest[..end].trim();
+        if !escaped && !path.starts_with(['$', '{']) && Path::new(path).is_relative() {
+            output.push_str(&dir.join(path).display().to_string());
+        } else {
+            output.push_str(&rest[..end]);
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` to a new directory named after `test`.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rdiff-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn duplicate_profiles_are_reported_at_each_definition() {
+        let dir = write_files(
+            "duplicate",
+            &[
+                ("main.yaml", "include: [more/*]\np: {}\nq: {}\n"),
+                ("more/a.yaml", "r: {}\n\np:\n  extends: q\n"),
+                ("more/b.toml", "[q]\n"),
+            ],
+        );
+        let (config, _, issues) = read_config(&dir.join("main.yaml"), None).unwrap();
+
+        let mut profiles: Vec<_> = config.as_mapping().unwrap().keys().collect();
+        profiles.sort_by_key(|name| name.as_str());
+        assert_eq!(profiles, ["p", "q", "r"]);
+        let issues: Vec<_> = issues.iter().map(|i| i.to_string()).collect();
+        let file = |name: &str| dir.join(name).display().to_string();
+        assert_eq!(
+            issues,
+            [
+                format!(
+                    "{}:3:1: error: p: duplicate profile, first defined at {}:2:1",
+                    file("more/a.yaml"),
+                    file("main.yaml")
+                ),
+                format!(
+                    "{}: error: q: duplicate profile, first defined at {}:3:1",
+                    file("more/b.toml"),
+                    file("main.yaml")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn issues_are_located_in_the_file_setting_the_value() {
+        use crate::{DiffConfig, LoadConfig};
+
+        let dir = write_files(
+            "locations",
+            &[
+                (
+                    "main.yaml",
+                    "include: [more.yaml]\nq:\n  req1: {url: \"ftp://b.test\"}\n  req2: {url: \"http://b.test\"}\n",
+                ),
+                (
+                    "more.yaml",
+                    "p:\n  req1:\n    url: http://a.test\n    client: {timeout: 1x}\n  req2: {url: \"http://b.test\"}\n",
+                ),
+            ],
+        );
+        let path = dir.join("main.yaml");
+        let issues = DiffConfig::lint(path.to_str().unwrap(), None, &[]).unwrap();
+
+        let issues: Vec<_> = issues.iter().map(|i| i.to_string()).collect();
+        let file = |name: &str| dir.join(name).display().to_string();
+        assert_eq!(
+            issues,
+            [
+                format!(
+                    "{}:3:10: error: q.req1.url: URL scheme must be http or https but got `ftp`",
+                    file("main.yaml")
+                ),
+                format!(
+                    "{}:4:14: error: p.req1.client.timeout: invalid duration `1x`, expected e.g. `30s`, `500ms` or `2m`",
+                    file("more.yaml")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_secrets_are_relative_to_the_config_file() {
+        let dir = Path::new("/etc/rdiff");
+        let cases = [
+            ("${file:token}", "${file:/etc/rdiff/token}"),
+            (
+                "Bearer ${file: secrets/token }!",
+                "Bearer ${file:/etc/rdiff/secrets/token}!",
+            ),
+            ("${file:/run/token}", "${file:/run/token}"),
+            ("${file:${HOME}/token}", "${file:${HOME}/token}"),
+            ("$${file:token}", "$${file:token}"),
+            (
+                "${file:a}:${file:b}",
+                "${file:/etc/rdiff/a}:${file:/etc/rdiff/b}",
+            ),
+            ("${FILE:-x}", "${FILE:-x}"),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(resolve_file_secrets(s, dir), expected, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use anyhow::{Result, anyhow};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+use super::issues::Issues;
+use super::{RESERVED_KEYS, Validateable};
+
+const DEFAULTS: &str = "defaults";
+const EXTENDS: &str = "extends";
+const HEADERS: &str = "headers";
+const AUTH: &str = "auth";
+const CLIENT: &str = "client";
+const REQ1: &str = "req1";
+const REQ2: &str = "req2";
+const REQUEST: &str = "request";
+const TARGETS: &str = "targets";
+
+/// Merge the top-level `defaults` and the profile named by `extends` into every profile.
+/// Mappings are merged recursively, other values of the profile replace inherited ones,
+/// and `null` removes an inherited value. An `auth` block with another `type`
+/// replaces the inherited one.
+/// A diff profile written with `request` and `targets` applies inherited `req1` and `req2`
+/// to its first and second target, and one written with `req1` and `req2` merges an
+/// inherited `request` into both, ignoring inherited `targets`.
+pub fn inherit_config(config: &mut Value, issues: &mut Issues) {
+    let Value::Mapping(profiles) = config else {
+        return;
+    };
+
+    let defaults = match profiles.remove(DEFAULTS) {
+        Some(Value::Mapping(defaults)) => defaults,
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => {
+            issues.at(DEFAULTS, |issues| issues.error("must be a mapping"));
+            Mapping::new()
+        }
+    };
+    let raw: HashMap<String, Mapping> = profiles
+        .iter()
+        .filter_map(|(name, profile)| match (name.as_str(), profile) {
+            (Some(name), Value::Mapping(profile)) if !RESERVED_KEYS.contains(&name) => {
+                Some((name.to_string(), profile.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut inheritance = Inheritance {
+        raw: &raw,
+        defaults: &defaults,
+        resolved: HashMap::new(),
+    };
+    inheritance.check(issues);
+    // Profiles with a broken chain were reported, at the profile where it breaks.
+    profiles.retain(|name, _| {
+        name.as_str()
+            .is_none_or(|name| !raw.contains_key(name) || inheritance.chain(name).is_ok())
+    });
+    for (name, profile) in profiles.iter_mut() {
+        if let Some(name) = name.as_str()
+            && raw.contains_key(name)
+        {
+            *profile = Value::Mapping(inheritance.resolve(name));
+        }
+    }
+}
+
+struct Inheritance<'a> {
+    raw: &'a HashMap<String, Mapping>,
+    defaults: &'a Mapping,
+    resolved: HashMap<String, Mapping>,
+}
+
+/// Why the inheritance chain of a profile is broken.
+enum ChainError {
+    /// The `extends` of this profile is invalid.
+    Parent(anyhow::Error),
+    /// The profile is part of this cycle.
+    Cycle(Vec<String>),
+    /// A profile the profile inherits from is broken.
+    Ancestor,
+}
+
+impl Inheritance<'_> {
+    /// Get the name of the profile a profile extends.
+    fn parent(&self, name: &str) -> Result<Option<&str>> {
+        match self.raw[name].get(EXTENDS) {
+            Some(Value::String(parent)) if self.raw.contains_key(parent) => Ok(Some(parent)),
+            Some(Value::String(parent)) => Err(anyhow!("unknown profile `{}`", parent)),
+            Some(Value::Null) | None => Ok(None),
+            Some(_) => Err(anyhow!("must be a profile name")),
+        }
+    }
+
+    /// Get the names of the profiles a profile inherits from, nearest first.
+    fn chain(&self, name: &str) -> Result<Vec<&str>, ChainError> {
+        let mut chain = vec![];
+        let mut current = self.parent(name).map_err(ChainError::Parent)?;
+        while let Some(parent) = current {
+            if parent == name {
+                let mut cycle: Vec<String> = vec![name.to_string()];
+                cycle.extend(chain.iter().map(|p: &&str| p.to_string()));
+                cycle.push(name.to_string());
+                return Err(ChainError::Cycle(cycle));
+            }
+            if chain.contains(&parent) {
+                return Err(ChainError::Ancestor);
+            }
+            chain.push(parent);
+            current = self.parent(parent).map_err(|_| ChainError::Ancestor)?;
+        }
+        Ok(chain)
+    }
+
+    /// Merge a validated profile into its parent, or into the defaults.
+    fn resolve(&mut self, name: &str) -> Mapping {
+        if let Some(profile) = self.resolved.get(name) {
+            return profile.clone();
+        }
+
+        let mut profile = self.raw[name].clone();
+        let mut base = match profile.remove(EXTENDS) {
+            Some(Value::String(parent)) => self.resolve(&parent),
+            _ => self.defaults.clone(),
+        };
+        let (shared, sides) = (
+            has_any(&profile, &[REQUEST, TARGETS]),
+            has_any(&profile, &[REQ1, REQ2]),
+        );
+        merge(&mut base, profile);
+        match (shared, sides) {
+            (true, false) => sides_into_targets(&mut base),
+            (false, true) => request_into_sides(&mut base),
+            _ => {}
+        }
+
+        self.resolved.insert(name.to_string(), base.clone());
+        base
+    }
+}
+
+impl Validateable for Inheritance<'_> {
+    /// Check that every `extends` names a profile and no profile extends itself.
+    fn check(&self, issues: &mut Issues) {
+        let mut names: Vec<&String> = self.raw.keys().collect();
+        names.sort();
+        for name in names {
+            let error = match self.chain(name) {
+                Err(ChainError::Parent(e)) => e,
+                Err(ChainError::Cycle(cycle)) => {
+                    anyhow!("profile inheritance cycle: {}", cycle.join(" -> "))
+                }
+                Ok(_) | Err(ChainError::Ancestor) => continue,
+            };
+            issues.at(name, |issues| {
+                issues.at(EXTENDS, |issues| issues.error(error))
+            });
+        }
+    }
+}
+
+/// Merge `overlay` into `base`, recursively for mappings.
+/// `null` in the overlay removes the value from `base`, except in `client` where it
+/// is kept to disable a timeout.
+/// Header names in `headers` are compared case-insensitively, and an `auth` block
+/// of another `type` replaces the one in `base`.
+pub fn merge(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Mapping(base)), Value::Mapping(overlay)) if key == CLIENT => {
+                base.extend(overlay)
+            }
+            (_, Value::Null) => {
+                base.remove(&key);
+            }
+            (Some(Value::Mapping(base)), Value::Mapping(overlay)) if key == HEADERS => {
+                merge_headers(base, overlay)
+            }
+            (Some(Value::Mapping(base)), Value::Mapping(overlay))
+                if key != AUTH || same_type(base, &overlay) =>
+            {
+                merge(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merge `overlay` headers into `base`, replacing headers whatever the case of their name.
+fn merge_headers(base: &mut Mapping, overlay: Mapping) {
+    for (name, value) in overlay {
+        if let Some(name) = name.as_str() {
+            base.retain(|key, _| {
+                !key.as_str()
+                    .is_some_and(|key| key.eq_ignore_ascii_case(name))
+            });
+        }
+        if !value.is_null() {
+            base.insert(name, value);
+        }
+    }
+}
+
+fn has_any(profile: &Mapping, keys: &[&str]) -> bool {
+    keys.iter().any(|key| profile.contains_key(*key))
+}
+
+/// Move inherited `req1` and `req2` into the first and second `targets`,
+/// below the overrides of each target.
+fn sides_into_targets(profile: &mut Mapping) {
+    let mut targets = match profile.get(TARGETS) {
+        Some(Value::Sequence(targets)) => targets.clone(),
+        None => vec![],
+        Some(_) => return,
+    };
+    for (index, side) in [REQ1, REQ2].into_iter().enumerate() {
+        let Some(Value::Mapping(mut merged)) = profile.remove(side) else {
+            continue;
+        };
+        // A side without a target is dropped, the count of `targets` is reported instead.
+        let own = match targets.get(index) {
+            Some(Value::String(url)) => Mapping::from_iter([("url".into(), url.clone().into())]),
+            Some(Value::Mapping(own)) => own.clone(),
+            _ => continue,
+        };
+        merge(&mut merged, own);
+        targets[index] = Value::Mapping(merged);
+    }
+    profile.insert(TARGETS.into(), Value::Sequence(targets));
+}
+
+/// Merge an inherited `request` below `req1` and `req2`, dropping inherited `targets`.
+fn request_into_sides(profile: &mut Mapping) {
+    let Some(Value::Mapping(request)) = profile.get(REQUEST) else {
+        return;
+    };
+    let request = request.clone();
+    for side in [REQ1, REQ2] {
+        if let Some(Value::Mapping(own)) = profile.get_mut(side) {
+            let mut merged = request.clone();
+            merge(&mut merged, std::mem::take(own));
+            *own = merged;
+        }
+    }
+    profile.remove(REQUEST);
+    profile.remove(TARGETS);
+}
+
+/// Whether two mappings have the same `type`, or don't have one.
+fn same_type(base: &Mapping, overlay: &Mapping) -> bool {
+    match (base.get("type"), overlay.get("type")) {
+        (Some(base), Some(overlay)) => base == overlay,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inherit a YAML config, returning it and its issues.
+    fn inherit(yaml: &str) -> (Value, Vec<String>) {
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        let mut issues = Issues::default();
+        inherit_config(&mut config, &mut issues);
+        (config, issues.iter().map(|i| i.to_string()).collect())
+    }
+
+    #[test]
+    fn profiles_merge_defaults_and_parents() {
+        let (config, issues) = inherit(
+            r#"
+defaults:
+  req1: {url: http://a.test, params: {a: 1, b: 2}}
+  tags: [all]
+base:
+  extends: null
+  req1: {params: {b: null, c: 3}}
+child:
+  extends: base
+  req1: {url: http://b.test}
+  tags: [child]
+"#,
+        );
+        assert_eq!(issues, Vec::<String>::new());
+        let expected: Value = serde_yaml::from_str(
+            r#"
+base:
+  req1: {url: http://a.test, params: {a: 1, c: 3}}
+  tags: [all]
+child:
+  req1: {url: http://b.test, params: {a: 1, c: 3}}
+  tags: [child]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn headers_are_overridden_whatever_their_case() {
+        let (config, _) = inherit(
+            r#"
+defaults:
+  req1: {headers: {Authorization: a, X-Default: d, X-Removed: r}}
+base:
+  req1: {headers: {authorization: b, x-base: b}}
+child:
+  extends: base
+  req1: {headers: {AUTHORIZATION: c, x-removed: null}}
+"#,
+        );
+        let headers = |name: &str| serde_yaml::to_string(&config[name]["req1"]["headers"]).unwrap();
+        assert_eq!(
+            headers("base"),
+            "X-Default: d\nX-Removed: r\nauthorization: b\nx-base: b\n"
+        );
+        assert_eq!(
+            headers("child"),
+            "X-Default: d\nx-base: b\nAUTHORIZATION: c\n"
+        );
+    }
+
+    #[test]
+    fn auth_of_another_type_replaces_the_inherited_one() {
+        let (config, _) = inherit(
+            r#"
+defaults:
+  req1: {auth: {type: basic, username: u, password: p}}
+same:
+  req1: {auth: {type: basic, password: q}}
+other:
+  req1: {auth: {type: bearer, token: t}}
+"#,
+        );
+        let auth = |name: &str| serde_yaml::to_string(&config[name]["req1"]["auth"]).unwrap();
+        assert_eq!(auth("same"), "type: basic\nusername: u\npassword: q\n");
+        assert_eq!(auth("other"), "type: bearer\ntoken: t\n");
+    }
+
+    #[test]
+    fn broken_chains_are_reported_where_they_break() {
+        let (config, issues) = inherit(
+            r#"
+a: {extends: b}
+b: {extends: c}
+c: {extends: a}
+d: {extends: missing}
+e: {extends: d}
+f: {extends: [a]}
+g: {extends: g}
+ok: {}
+"#,
+        );
+        assert_eq!(
+            issues,
+            [
+                "error: a.extends: profile inheritance cycle: a -> b -> c -> a",
+                "error: b.extends: profile inheritance cycle: b -> c -> a -> b",
+                "error: c.extends: profile inheritance cycle: c -> a -> b -> c",
+                "error: d.extends: unknown profile `missing`",
+                "error: f.extends: must be a profile name",
+                "error: g.extends: profile inheritance cycle: g -> g",
+            ]
+        );
+        // Profiles inheriting from a broken one are dropped without another error.
+        let profiles: Vec<_> = config.as_mapping().unwrap().keys().collect();
+        assert_eq!(profiles, ["ok"]);
+    }
+}
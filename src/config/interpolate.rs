@@ -0,0 +1,221 @@
+use anyhow::{Context, Result, anyhow};
+use serde_yaml::Value;
+use std::sync::{LazyLock, RwLock};
+
+use super::issues::Issues;
+
+/// Values resolved from secret files and variables, masked in printed output.
+static SECRETS: LazyLock<RwLock<Vec<String>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+pub(super) const MASK: &str = "******";
+/// Keys of a request whose resolved variables are masked, as they may hold credentials.
+const MASKED_KEYS: [&str; 6] = [
+    "headers",
+    "auth",
+    "params",
+    "body",
+    "body_raw",
+    "body_base64",
+];
+
+/// Resolve `${VAR}`, `${VAR:-default}`, `${secret:VAR}` and `${file:path/to/secret}`,
+/// relative to the config file, in every string of a config, where top-level keys
+/// are profile names.
+/// Variables resolved in headers, auth, params and bodies, `secret:` variables and
+/// files are masked in printed output. Use `$${` for a literal `${`.
+pub fn interpolate_config(config: &mut Value, issues: &mut Issues) {
+    match config {
+        Value::Mapping(profiles) => {
+            issues.retain_profiles(profiles, |_, profile, issues| {
+                interpolate_value(profile, false, issues)
+            });
+        }
+        value => interpolate_value(value, false, issues),
+    }
+}
+
+/// Interpolate every string of `value`, which is `masked` if it is in a masked key.
+fn interpolate_value(value: &mut Value, masked: bool, issues: &mut Issues) {
+    match value {
+        Value::String(s) if s.contains('$') => match interpolate_str(s, masked) {
+            Ok(interpolated) => *s = interpolated,
+            Err(e) => issues.error(format!("{:#}", e)),
+        },
+        Value::Sequence(seq) => {
+            for (index, value) in seq.iter_mut().enumerate() {
+                issues.at_index(index, |issues| interpolate_value(value, masked, issues));
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, value) in map.iter_mut() {
+                let masked = masked || key.as_str().is_some_and(|key| MASKED_KEYS.contains(&key));
+                issues.at_key(key, |issues| interpolate_value(value, masked, issues));
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, masked, issues),
+        _ => {}
+    }
+}
+
+fn interpolate_str(s: &str, masked: bool) -> Result<String> {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if let Some(r) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("${") {
+            let end = r
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed `${{` in `{}`", s))?;
+            output.push_str(&resolve(&r[..end], masked)?);
+            rest = &r[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+fn resolve(expr: &str, masked: bool) -> Result<String> {
+    let expr = expr.trim();
+    if let Some(path) = expr.strip_prefix("file:") {
+        let path = path.trim();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read secret file `{}`", path))?;
+        let secret = content.trim_end_matches(['\r', '\n']).to_string();
+        add_secret(&secret);
+        return Ok(secret);
+    }
+
+    let (secret, expr) = match expr.strip_prefix("secret:") {
+        Some(expr) => (true, expr.trim()),
+        None => (false, expr),
+    };
+    let (name, default) = match expr.split_once(":-") {
+        Some((name, default)) => (name.trim(), Some(default)),
+        None => (expr, None),
+    };
+    if name.is_empty() {
+        return Err(anyhow!("empty variable name in `${{{}}}`", expr));
+    }
+    match (std::env::var(name), default) {
+        (Ok(value), _) if !value.is_empty() => {
+            if secret || masked {
+                add_secret(&value);
+            }
+            Ok(value)
+        }
+        (_, Some(default)) => Ok(default.to_string()),
+        (Ok(value), None) => Ok(value),
+        (Err(_), None) => Err(anyhow!("environment variable `{}` is not set", name)),
+    }
+}
+
+/// Mask `secret` in printed output, e.g. a token read when a request is sent.
+pub(super) fn add_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    if let Ok(mut secrets) = SECRETS.write()
+        && !secrets.iter().any(|s| s == secret)
+    {
+        secrets.push(secret.to_string());
+        // Mask longer secrets first, in case one contains another.
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Replace every secret resolved from the config or when sending with a mask.
+pub fn mask_secrets(text: &str) -> String {
+    let Ok(secrets) = SECRETS.read() else {
+        return text.to_string();
+    };
+    secrets
+        .iter()
+        .fold(text.to_string(), |text, secret| text.replace(secret, MASK))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_are_masked_in_requests_but_not_urls() {
+        // Names and values are unique to this test, as variables and secrets are global.
+        let vars = [
+            ("RDIFF_MASK_HOST", "mask-host.test"),
+            ("RDIFF_MASK_HEADER", "mask-header-token"),
+            ("RDIFF_MASK_AUTH", "mask-auth-password"),
+            ("RDIFF_MASK_PARAM", "mask-param-key"),
+            ("RDIFF_MASK_BODY", "mask-body-value"),
+            ("RDIFF_MASK_SECRET", "mask-secret-path"),
+        ];
+        for (name, value) in vars {
+            // SAFETY: no other test reads or writes these variables.
+            unsafe { std::env::set_var(name, value) };
+        }
+        let mut config: Value = serde_yaml::from_str(
+            r#"
+p:
+  req1:
+    url: http://${RDIFF_MASK_HOST}/${secret:RDIFF_MASK_SECRET}
+    headers: {authorization: "Bearer ${RDIFF_MASK_HEADER}"}
+    auth: {type: basic, username: u, password: "${RDIFF_MASK_AUTH}"}
+    params: {key: "${RDIFF_MASK_PARAM}", page: "${RDIFF_MASK_UNSET:-mask-default}"}
+    body: {items: [{value: "${RDIFF_MASK_BODY}"}]}
+"#,
+        )
+        .unwrap();
+        let mut issues = Issues::default();
+        interpolate_config(&mut config, &mut issues);
+        assert!(issues.is_empty());
+
+        let printed = serde_yaml::to_string(&config).unwrap();
+        let masked = mask_secrets(&printed);
+        assert!(
+            masked.contains("url: http://mask-host.test/******"),
+            "{}",
+            masked
+        );
+        for (name, value) in vars.iter().skip(1) {
+            assert!(printed.contains(value), "{}: {}", name, printed);
+            assert!(!masked.contains(value), "{}: {}", name, masked);
+        }
+        assert!(masked.contains("page: mask-default"), "{}", masked);
+    }
+
+    #[test]
+    fn interpolate_str_resolves_defaults_and_escapes() {
+        let cases = [
+            ("plain $ text", "plain $ text"),
+            ("$${NOT_A_VAR}", "${NOT_A_VAR}"),
+            ("${RDIFF_UNSET_VAR:-fallback}", "fallback"),
+            ("a${ RDIFF_UNSET_VAR :-}b", "ab"),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(interpolate_str(s, false).unwrap(), expected, "{}", s);
+        }
+        let cases = [
+            (
+                "${RDIFF_UNSET_VAR}",
+                "environment variable `RDIFF_UNSET_VAR` is not set",
+            ),
+            ("${}", "empty variable name in `${}`"),
+            ("${RDIFF_UNSET_VAR", "unclosed `${` in `${RDIFF_UNSET_VAR`"),
+            (
+                "${file:/nonexistent/rdiff}",
+                "failed to read secret file `/nonexistent/rdiff`",
+            ),
+        ];
+        for (s, expected) in cases {
+            let err = interpolate_str(s, false).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use anyhow::{Result, anyhow};
+use console::style;
+use std::fmt;
+
+use super::source_map::SourceMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The config can't be loaded.
+    Error,
+    /// The config loads but likely doesn't do what was intended.
+    Warning,
+}
+
+/// Position of a key or value in a config file, starting at line and column 1,
+/// or line 0 for the whole file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A problem found in a config, at a path such as `todo.req1.headers`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+/// Every problem found while loading a config, so they are all reported at once.
+#[derive(Debug, Default)]
+pub struct Issues {
+    issues: Vec<Issue>,
+    /// Path of the value being checked.
+    path: Vec<String>,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl Location {
+    /// Location of a whole file, e.g. a TOML file whose values have no locations.
+    pub fn file(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            line: 0,
+            column: 0,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.file),
+            line => write!(f, "{}:{}:{}", self.file, line, self.column),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}: ", self.severity)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Issue {
+    /// Render the issue with a colored severity for the terminal.
+    pub fn styled(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => style(self.severity).red().bold(),
+            Severity::Warning => style(self.severity).yellow().bold(),
+        };
+        let mut text = String::new();
+        if let Some(location) = &self.location {
+            text.push_str(&format!("{}: ", style(location).bold()));
+        }
+        text.push_str(&format!("{}: ", severity));
+        if !self.path.is_empty() {
+            text.push_str(&format!("{}: ", style(&self.path).cyan()));
+        }
+        text.push_str(&self.message);
+        text
+    }
+}
+
+impl Issues {
+    pub fn error(&mut self, message: impl fmt::Display) {
+        self.push(Severity::Error, message);
+    }
+
+    /// Report an error at a known location, e.g. of a value which is not in the
+    /// config as it's shadowed by another one.
+    pub(super) fn error_at(&mut self, location: Location, message: impl fmt::Display) {
+        self.push(Severity::Error, message);
+        if let Some(issue) = self.issues.last_mut() {
+            issue.location = Some(location);
+        }
+    }
+
+    pub fn warning(&mut self, message: impl fmt::Display) {
+        self.push(Severity::Warning, message);
+    }
+
+    /// Run `f` with `key` appended to the path of the issues it reports.
+    pub fn at(&mut self, key: impl fmt::Display, f: impl FnOnce(&mut Issues)) {
+        self.path.push(key.to_string());
+        f(self);
+        self.path.pop();
+    }
+
+    /// Run `f` with the sequence index `index` appended to the path.
+    pub fn at_index(&mut self, index: usize, f: impl FnOnce(&mut Issues)) {
+        self.at(format!("[{}]", index), f)
+    }
+
+    /// Run `f` with a mapping key appended to the path.
+    pub fn at_key(&mut self, key: &serde_yaml::Value, f: impl FnOnce(&mut Issues)) {
+        match key {
+            serde_yaml::Value::String(key) => self.at(key, f),
+            serde_yaml::Value::Number(key) => self.at(key, f),
+            serde_yaml::Value::Bool(key) => self.at(key, f),
+            _ => self.at("?", f),
+        }
+    }
+
+    /// Check every profile of a config with `f`, at the path of its name.
+    /// Profiles with errors are dropped, so later checks don't report the same problem again.
+    pub fn retain_profiles(
+        &mut self,
+        profiles: &mut serde_yaml::Mapping,
+        mut f: impl FnMut(&str, &mut serde_yaml::Value, &mut Issues),
+    ) {
+        profiles.retain(|name, profile| {
+            let Some(name) = name.as_str() else {
+                return true;
+            };
+            let errors = self.errors().count();
+            self.at(name, |issues| f(name, profile, issues));
+            self.errors().count() == errors
+        });
+    }
+
+    /// Report a deserialization error at the path it occurred.
+    pub fn deserialize_error(&mut self, err: serde_path_to_error::Error<serde_yaml::Error>) {
+        let depth = self.path.len();
+        self.path.extend(path_segments(err.path()));
+        self.error(err.inner());
+        self.path.truncate(depth);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.iter().filter(|i| i.severity == Severity::Warning)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Fail with every error, if any.
+    pub fn into_result(self) -> Result<()> {
+        let errors: Vec<String> = self.errors().map(Issue::to_string).collect();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(anyhow!("invalid config: {}", errors[0])),
+            n => Err(anyhow!(
+                "invalid config with {} errors:\n  {}",
+                n,
+                errors.join("\n  ")
+            )),
+        }
+    }
+
+    /// Look up the location of every issue in the config files, unless it has one
+    /// already, and sort them by it.
+    /// An issue is located at the closest value of its path found in a file.
+    pub(super) fn locate(&mut self, locations: &SourceMap) {
+        for issue in self
+            .issues
+            .iter_mut()
+            .filter(|issue| issue.location.is_none())
+        {
+            issue.location = locations.locate(&issue.path).cloned();
+        }
+        // Issues of a file come in order, and those without a location last.
+        self.issues
+            .sort_by_key(|issue| (issue.location.is_none(), issue.location.clone()));
+    }
+
+    fn push(&mut self, severity: Severity, message: impl fmt::Display) {
+        self.issues.push(Issue {
+            severity,
+            path: join_path(&self.path),
+            message: message.to_string(),
+            location: None,
+        });
+    }
+}
+
+/// Segments of the path to a deserialization error, as used by `join_path`.
+pub(super) fn path_segments(path: &serde_path_to_error::Path) -> Vec<String> {
+    path.iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Seq { index } => Some(format!("[{}]", index)),
+            serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Join path segments into e.g. `todo.resp.normalize[0].pattern`.
+pub(super) fn join_path<S: AsRef<str>>(segments: &[S]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        let segment = segment.as_ref();
+        if !path.is_empty() && !segment.starts_with('[') {
+            path.push('.');
+        }
+        path.push_str(segment);
+    }
+    path
+}
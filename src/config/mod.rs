@@ -1,72 +1,305 @@
+mod auth;
+mod client;
+mod discover;
+mod duration;
+mod format;
+mod headers;
+mod include;
+mod inherit;
+mod interpolate;
+mod issues;
+mod multipart;
+mod normalize;
 mod rdiff;
+mod retry;
+mod schema;
+mod source_map;
+mod template;
 mod xreq;
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-pub use rdiff::{DiffConfig, DiffProfile, ResponseProfile};
+pub use auth::{Auth, Grant, OAuth2};
+pub use client::{ClientConfig, HttpVersion};
+pub use discover::{ConfigLocation, ConfigSource, discover_config};
+pub use format::ConfigFormat;
+use include::{include_config, read_config};
+use inherit::inherit_config;
+use interpolate::interpolate_config;
+pub use interpolate::mask_secrets;
+pub use issues::{Issue, Issues, Location, Severity};
+pub use multipart::FilePart;
+pub use normalize::{NormalizeAction, NormalizeRule};
+pub use rdiff::{DiffConfig, DiffMode, DiffProfile, ResponseProfile};
+pub use retry::RetryPolicy;
+pub use schema::config_schema;
+use schemars::JsonSchema;
+use source_map::SourceMap;
+use std::collections::HashMap;
 use std::fmt::{Debug, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use template::render_config;
 pub use xreq::RequestConfig;
 
 use async_trait::async_trait;
+use base64::prelude::{BASE64_STANDARD, Engine};
 use reqwest::{
-    Client, Method, Response,
+    Method, Response,
     header::{self, HeaderMap, HeaderName, HeaderValue},
 };
 use serde_json::json;
 use url::Url;
 
-use crate::ExtraArgs;
+use crate::{ConfigError, ExtraArgs, JsonPath};
+
+/// Top-level keys of a config which are settings rather than profile names.
+const RESERVED_KEYS: [&str; 3] = ["client", "vars", "defaults"];
 
 #[async_trait]
 pub trait LoadConfig
 where
-    Self: Sized + Validateable + DeserializeOwned + Debug,
+    Self: Sized + Validateable + DeserializeOwned + Debug + Send + 'static,
 {
-    /// Loads a configuration from a YAML file.
+    /// Default file name of the config, searched by `discover` with every config extension.
+    const FILE_NAME: &'static str;
+    /// Environment variable with the path to the config.
+    const ENV_VAR: &'static str;
+
+    /// Type of the profiles of the config.
+    type Profile: DeserializeOwned + Validateable;
+
+    /// Find the config file, see `discover_config` for the search order.
+    fn discover(flag: Option<&str>) -> Result<ConfigLocation> {
+        discover_config(flag, Self::ENV_VAR, Self::FILE_NAME)
+    }
+
+    /// Loads a configuration from a file in the format of its extension,
+    /// or from every config file of a directory.
     async fn load_yaml(path: &str) -> Result<Self> {
-        let content = tokio::fs::read_to_string(path).await?;
-        Self::from_yaml(&content)
+        Self::load(path, None, &[]).await
+    }
+
+    /// Loads a configuration from a file or directory, overriding template variables.
+    /// The format is detected from the file extension unless `format` is given.
+    /// Files matching the `include` globs of a file, relative to it, are loaded too.
+    /// Fails with every error found in the config.
+    async fn load(
+        path: &str,
+        format: Option<ConfigFormat>,
+        vars: &[(String, String)],
+    ) -> Result<Self> {
+        // Included and secret files are read on a blocking thread, off the runtime.
+        let (path, vars) = (PathBuf::from(path), vars.to_vec());
+        tokio::task::spawn_blocking(move || {
+            let (value, locations, issues) = read_config(&path, format)?;
+            into_config(check_config(value, &vars, &locations, issues))
+        })
+        .await?
+    }
+
+    /// Load a configuration like `load`, but return every error and warning found
+    /// with its location. Only fails if a file can't be read or parsed.
+    fn lint(path: &str, format: Option<ConfigFormat>, vars: &[(String, String)]) -> Result<Issues> {
+        let (value, locations, issues) = read_config(Path::new(path), format)?;
+        let (_, issues) = check_config::<Self>(value, vars, &locations, issues);
+        Ok(issues)
     }
 
     /// Parse a YAML string into a configuration.
     fn from_yaml(content: &str) -> Result<Self> {
-        let config: Self = serde_yaml::from_str(content)?;
-        config.validate()?;
-        Ok(config)
+        Self::parse(content, ConfigFormat::Yaml, &[])
+    }
+
+    /// Parse a string in the given format into a configuration, overriding template variables.
+    /// `include` globs are relative to the current directory.
+    fn parse(content: &str, format: ConfigFormat, vars: &[(String, String)]) -> Result<Self> {
+        let (value, locations, issues) = include_config(content, format, Path::new("."))?;
+        into_config(check_config(value, vars, &locations, issues))
+    }
+
+    /// Build a configuration from a parsed document, overriding template variables.
+    /// `${VAR}`, `${VAR:-default}`, `${secret:VAR}` and `${file:path}` are resolved in every string,
+    /// profiles inherit from `defaults` and the profile they `extends`,
+    /// then `{{name}}` is rendered from `vars` and the `vars` blocks of the config.
+    fn from_value_with_vars(value: serde_yaml::Value, vars: &[(String, String)]) -> Result<Self> {
+        into_config(check_config(
+            value,
+            vars,
+            &SourceMap::default(),
+            Issues::default(),
+        ))
+    }
+
+    /// Apply config-wide settings to every profile after parsing.
+    fn resolve(&mut self) -> Result<()> {
+        Ok(())
     }
 }
 
+/// Build a configuration from a parsed document, collecting every problem on the way
+/// after those found reading it. The configuration is only built if the document
+/// could be deserialized.
+fn check_config<T: LoadConfig>(
+    mut value: serde_yaml::Value,
+    vars: &[(String, String)],
+    locations: &SourceMap,
+    mut issues: Issues,
+) -> (Option<T>, Issues) {
+    interpolate_config(&mut value, &mut issues);
+    inherit_config(&mut value, &mut issues);
+    render_config(&mut value, vars, &mut issues);
+    let config = deserialize_config::<T>(value, &mut issues).map(|mut config| {
+        if let Err(e) = config.resolve() {
+            issues.error(format!("{:#}", e));
+        }
+        config.check(&mut issues);
+        config
+    });
+    issues.locate(locations);
+    (config, issues)
+}
+
+/// Deserialize each profile on its own first, to report the errors of every profile.
+fn deserialize_config<T: LoadConfig>(
+    mut value: serde_yaml::Value,
+    issues: &mut Issues,
+) -> Option<T> {
+    if let serde_yaml::Value::Mapping(profiles) = &mut value {
+        issues.retain_profiles(profiles, |name, profile, issues| {
+            if RESERVED_KEYS.contains(&name) {
+                return;
+            }
+            // Valid profiles are checked with the config, which drops the others.
+            let errors = issues.errors().count();
+            let profile = deserialize_profile::<T::Profile>(profile.clone(), issues);
+            if issues.errors().count() > errors
+                && let Some(profile) = profile
+            {
+                profile.check(issues);
+            }
+        });
+    }
+    match serde_path_to_error::deserialize(value) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            issues.deserialize_error(e);
+            None
+        }
+    }
+}
+
+/// Deserialize a profile, dropping each key that fails and trying again to report
+/// the errors of the other keys too. Stops at an error caused by a dropped key,
+/// e.g. a missing required field.
+fn deserialize_profile<P: DeserializeOwned>(
+    mut profile: serde_yaml::Value,
+    issues: &mut Issues,
+) -> Option<P> {
+    let mut dropped: Vec<Vec<String>> = Vec::new();
+    loop {
+        let err = match serde_path_to_error::deserialize(profile.clone()) {
+            Ok(profile) => return Some(profile),
+            Err(err) => err,
+        };
+        let mut path = issues::path_segments(err.path());
+        if dropped.iter().any(|parent| parent.starts_with(&path)) {
+            return None;
+        }
+        issues.deserialize_error(err);
+
+        // Drop the closest mapping key, as sequence items can't be removed without
+        // shifting the paths of the following items.
+        let key = path.iter().rposition(|s| !s.starts_with('['))?;
+        path.truncate(key + 1);
+        let key = path.pop()?;
+        let parent = value_at(&mut profile, &path)?.as_mapping_mut()?;
+        parent.remove(&key)?;
+        dropped.push(path);
+    }
+}
+
+/// Get the value at a path of `join_path` segments.
+fn value_at<'a>(
+    value: &'a mut serde_yaml::Value,
+    path: &[String],
+) -> Option<&'a mut serde_yaml::Value> {
+    path.iter().try_fold(value, |value, segment| {
+        match segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(index) => value.get_mut(index.parse::<usize>().ok()?),
+            None => value.get_mut(segment.as_str()),
+        }
+    })
+}
+
+fn into_config<T>((config, issues): (Option<T>, Issues)) -> Result<T> {
+    issues.into_result()?;
+    config.ok_or_else(|| anyhow!("invalid config"))
+}
+
 pub trait Validateable {
-    /// Validates the configuration.
-    fn validate(&self) -> Result<()>;
+    /// Report every problem of the configuration.
+    fn check(&self, issues: &mut Issues);
+
+    /// Validates the configuration, failing with every error found.
+    fn validate(&self) -> Result<()> {
+        let mut issues = Issues::default();
+        self.check(&mut issues);
+        issues.into_result()
+    }
 }
 
 pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct RequestProfile {
     /// HTTP method (GET, POST, etc.)
     /// Defaults to GET.
     #[serde(with = "http_serde::method", default)]
+    #[schemars(schema_with = "schema::method")]
     pub method: Method,
     /// URL to send the request to.
     pub url: Url,
     /// Http request parameters.
     /// Defaults to None.
     #[serde(skip_serializing_if = "empty_json_value", default)]
+    #[schemars(schema_with = "schema::json_object")]
     pub params: Option<serde_json::Value>,
-    #[serde(
-        with = "http_serde::header_map",
-        skip_serializing_if = "HeaderMap::is_empty",
-        default
-    )]
+    #[serde(with = "headers", skip_serializing_if = "HeaderMap::is_empty", default)]
+    #[schemars(schema_with = "schema::header_map")]
     pub headers: HeaderMap,
+    /// Authentication, which sets the `authorization` header unless it is set already.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub auth: Option<Auth>,
+    /// JSON body, an object, array or scalar. It is encoded for the content type:
+    /// as JSON by default, one line per item for `application/x-ndjson`, and a string
+    /// is sent as is for other content types.
     #[serde(skip_serializing_if = "empty_json_value", default)]
     pub body: Option<serde_json::Value>,
+    /// Text sent as the body as is, e.g. XML. Defaults to `text/plain`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body_raw: Option<String>,
+    /// File sent as the body, relative to the config file.
+    /// Defaults to `application/octet-stream`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body_file: Option<String>,
+    /// Base64 encoded bytes sent as the body, e.g. a protobuf message.
+    /// Defaults to `application/octet-stream`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body_base64: Option<String>,
+    /// HTTP client settings, merged with the config-wide `client` on load.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub retry: RetryPolicy,
+    /// Template variables for this request, rendered on load.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    #[schemars(schema_with = "schema::vars")]
+    pub vars: HashMap<String, String>,
 }
 
 impl FromStr for RequestProfile {
@@ -81,9 +314,9 @@ impl FromStr for RequestProfile {
         }
         url.set_query(None);
 
-        Ok(Self::new(
+        Ok(Self::from_url(
             Method::GET,
-            url.to_string(),
+            url,
             Some(params),
             HeaderMap::new(),
             None,
@@ -92,41 +325,183 @@ impl FromStr for RequestProfile {
 }
 
 #[derive(Debug)]
-pub struct ResponseExt(Response);
+pub struct ResponseExt {
+    resp: Response,
+    retries: Vec<String>,
+}
+
+/// A response after skip and normalize rules of a `ResponseProfile` were applied.
+#[derive(Debug, Clone)]
+pub struct FilteredResponse {
+    pub status: String,
+    pub headers: String,
+    pub body: serde_json::Value,
+}
+
+/// A request as it is sent, after extra arguments and default headers were applied.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveRequest {
+    #[serde(with = "http_serde::method")]
+    pub method: Method,
+    /// URL with query params.
+    pub url: String,
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    /// JSON bodies are kept as JSON, others as text.
+    pub body: serde_json::Value,
+}
+
+impl FilteredResponse {
+    /// Get the body as text, pretty-printing JSON bodies.
+    pub fn body_text(&self) -> Result<String> {
+        match &self.body {
+            serde_json::Value::String(text) => Ok(text.clone()),
+            json => Ok(serde_json::to_string_pretty(json)?),
+        }
+    }
+}
 
 impl RequestProfile {
+    /// Create a request from its parts.
+    /// Panics if `url` is invalid, see `from_url` to build a request from a parsed `Url`.
     pub fn new(
         method: Method,
         url: String,
         params: Option<serde_json::Value>,
         headers: HeaderMap,
         body: Option<serde_json::Value>,
+    ) -> Self {
+        let url = Url::parse(&url).unwrap_or_else(|e| panic!("invalid URL `{}`: {}", url, e));
+        Self::from_url(method, url, params, headers, body)
+    }
+
+    /// Create a request from its parts with a parsed URL,
+    /// so an invalid URL fails where it is parsed instead of here.
+    pub fn from_url(
+        method: Method,
+        url: Url,
+        params: Option<serde_json::Value>,
+        headers: HeaderMap,
+        body: Option<serde_json::Value>,
     ) -> Self {
         Self {
             method,
-            url: Url::parse(&url).unwrap(),
+            url,
             params,
             headers,
+            auth: None,
             body,
+            body_raw: None,
+            body_file: None,
+            body_base64: None,
+            client: ClientConfig::default(),
+            retry: RetryPolicy::default(),
+            vars: HashMap::new(),
         }
     }
 
     /// Send request with current `RequestProfile`.
-    /// Return an extension response.
+    /// Return an extension response, or an error listing the attempts which were retried.
     pub async fn send(&self, args: &ExtraArgs) -> Result<ResponseExt> {
-        let (headers, query, body) = self.generate(args)?;
-        let client = Client::new();
-
-        let req = client
-            .request(self.method.clone(), self.url.clone())
-            .query(&query)
-            .headers(headers)
-            .body(body)
+        let (mut headers, query, body) = self.generate(args).context(ConfigError)?;
+        let body = match body {
+            RequestBody::Multipart(_) => {
+                // Set by reqwest, with the boundary of the form.
+                headers.remove(header::CONTENT_TYPE);
+                body
+            }
+            RequestBody::File(path) => RequestBody::Bytes(
+                tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("failed to read body file {}", path))
+                    .context(ConfigError)?,
+            ),
+            body => body,
+        };
+        let client = self.client.client().context(ConfigError)?;
+        let max_attempts = self.retry.max_attempts();
+
+        let mut retries = Vec::new();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut headers = headers.clone();
+            if let Some(auth) = &self.auth
+                && !headers.contains_key(header::AUTHORIZATION)
+            {
+                // Fetched for every attempt, as a token may expire while retrying.
+                let auth = auth.header(&client).await;
+                headers.insert(header::AUTHORIZATION, with_retries(auth, &retries)?);
+            }
+            let req = client
+                .request(self.method.clone(), self.url.clone())
+                .query(&query)
+                .headers(headers);
+            let req = match &body {
+                RequestBody::Text(text) => req.body(text.clone()),
+                RequestBody::Bytes(bytes) => req.body(bytes.clone()),
+                RequestBody::File(_) => unreachable!("body files are read before sending"),
+                RequestBody::Multipart(fields) => {
+                    req.multipart(multipart::form(fields).await.context(ConfigError)?)
+                }
+            }
             .build()?;
 
-        let resp = client.execute(req).await?;
+            let reason = match client.execute(req).await {
+                Ok(resp) if attempt < max_attempts && self.retry.retry_status(resp.status()) => {
+                    format!("status {}", resp.status())
+                }
+                Ok(resp) => return Ok(ResponseExt { resp, retries }),
+                Err(e) if attempt < max_attempts && self.retry.retry_error(&e) => e.to_string(),
+                Err(e) if attempt > 1 => {
+                    let e = Err(e).context(format!("request failed after {} attempts", attempt));
+                    return with_retries(e, &retries);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let delay = self.retry.delay(attempt);
+            retries.push(format!(
+                "{} {}: attempt {} failed with {}, retrying in {}ms",
+                self.method,
+                self.url,
+                attempt,
+                reason,
+                delay.as_millis()
+            ));
+            tokio::time::sleep(delay).await;
+        }
+    }
 
-        Ok(ResponseExt(resp))
+    /// Get the request as it is sent with extra arguments.
+    /// Credentials of `auth` are masked in the `authorization` header.
+    pub fn effective(&self, args: &ExtraArgs) -> Result<EffectiveRequest> {
+        let (mut headers, _, body) = self.generate(args)?;
+        if let Some(auth) = &self.auth
+            && !headers.contains_key(header::AUTHORIZATION)
+        {
+            headers.insert(header::AUTHORIZATION, auth.masked_header());
+        }
+        let content_type = get_content_type(&headers);
+        let body = match body {
+            RequestBody::Text(text) if content_type.as_deref().is_some_and(is_json) => {
+                serde_json::from_str(&text)?
+            }
+            RequestBody::Text(text) => serde_json::Value::String(text),
+            RequestBody::Bytes(bytes) => bytes_value(bytes),
+            RequestBody::File(path) => bytes_value(
+                std::fs::read(&path)
+                    .with_context(|| format!("failed to read body file {}", path))?,
+            ),
+            RequestBody::Multipart(fields) => fields,
+        };
+
+        Ok(EffectiveRequest {
+            method: self.method.clone(),
+            url: self.get_url(args)?,
+            headers,
+            body,
+        })
     }
 
     /// Get specfic url string with query params.
@@ -143,94 +518,235 @@ impl RequestProfile {
     }
 
     /// Generate headers, query params and body with extra arguments.
-    fn generate(&self, args: &ExtraArgs) -> Result<(HeaderMap, serde_json::Value, String)> {
+    fn generate(&self, args: &ExtraArgs) -> Result<(HeaderMap, serde_json::Value, RequestBody)> {
         let mut headers = self.headers.clone();
         let mut query = self.params.clone().unwrap_or_else(|| json!({}));
-        let mut body = self.body.clone().unwrap_or_else(|| json!({}));
-
         for (k, v) in &args.header {
-            headers.insert(HeaderName::from_str(k)?, HeaderValue::from_str(v)?);
-        }
-
-        // If the content type is not set, default to application/json.
-        if !headers.contains_key(header::CONTENT_TYPE) {
-            headers.insert(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/json"),
-            );
+            let name = HeaderName::from_str(k)?;
+            match v {
+                Some(v) => headers.insert(name, HeaderValue::from_str(v)?),
+                None => headers.remove(name),
+            };
         }
 
         for (k, v) in &args.query {
             query[k] = v.as_str().into();
         }
 
+        // Bodies which aren't JSON are sent as they are.
+        let raw = match (&self.body_raw, &self.body_file, &self.body_base64) {
+            (Some(text), ..) => Some(("text/plain", RequestBody::Text(text.clone()))),
+            (_, Some(path), _) => {
+                Some(("application/octet-stream", RequestBody::File(path.clone())))
+            }
+            (.., Some(data)) => Some((
+                "application/octet-stream",
+                RequestBody::Bytes(decode_base64(data)?),
+            )),
+            _ => None,
+        };
+        if let Some((content_type, body)) = raw {
+            if !args.body.is_empty() || !args.files.is_empty() {
+                return Err(anyhow!("body fields can only be set on a JSON body"));
+            }
+            if !headers.contains_key(header::CONTENT_TYPE) {
+                headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            }
+            return Ok((headers, query, body));
+        }
+
+        let mut body = self.body.clone().unwrap_or_else(|| json!({}));
         for (k, v) in &args.body {
-            body[k] = v.as_str().into();
+            let path: JsonPath = k.parse()?;
+            match v {
+                Some(v) => path.set(&mut body, v.clone())?,
+                None => path.remove(&mut body),
+            }
+        }
+
+        for (k, v) in &args.files {
+            let path: JsonPath = k.parse()?;
+            path.set(&mut body, serde_json::to_value(v.parse::<FilePart>()?)?)?;
+        }
+
+        // If the content type is not set, default to multipart/form-data
+        // for bodies with files, and to application/json otherwise.
+        if !headers.contains_key(header::CONTENT_TYPE) {
+            let content_type = if multipart::has_files(&body) {
+                "multipart/form-data"
+            } else {
+                "application/json"
+            };
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
         }
 
-        // Check the content type and serialize the body accordingly.
+        // Serialize the body according to the content type.
         let content_type = get_content_type(&headers);
-        match content_type.as_deref() {
-            Some("application/json") => {
-                let body = serde_json::to_string(&body)?;
-                Ok((headers, query, body))
+        let body = match content_type.as_deref() {
+            Some("application/x-www-form-urlencoded") => RequestBody::Text(
+                serde_urlencoded::to_string(&body).context("urlencoded body must be an object")?,
+            ),
+            Some("multipart/form-data") => RequestBody::Multipart(body),
+            Some("application/x-ndjson") => RequestBody::Text(ndjson(&body)?),
+            Some(content_type) if !is_json(content_type) && body.is_string() => {
+                RequestBody::Text(body.as_str().unwrap_or_default().to_string())
             }
-            Some("application/x-www-form-urlencoded" | "multipart/form-data") => {
-                let body = serde_urlencoded::to_string(&body)?;
-                Ok((headers, query, body))
-            }
-            _ => Err(anyhow!("Unsupported content type")),
-        }
+            _ => RequestBody::Text(serde_json::to_string(&body)?),
+        };
+        Ok((headers, query, body))
+    }
+
+    /// Whether the request has a body.
+    fn has_body(&self) -> bool {
+        !empty_json_value(&self.body)
+            || self.body_raw.is_some()
+            || self.body_file.is_some()
+            || self.body_base64.is_some()
     }
 }
 
+/// Body of a request, encoded for its content type.
+#[derive(Debug, Clone)]
+enum RequestBody {
+    Text(String),
+    Bytes(Vec<u8>),
+    /// Path of a file, read when the request is sent.
+    File(String),
+    /// Fields of a multipart form, built for every attempt as a form can only be sent once.
+    Multipart(serde_json::Value),
+}
+
 impl Validateable for RequestProfile {
-    fn validate(&self) -> Result<()> {
-        if let Some(ref params) = self.params {
-            if !params.is_object() {
-                return Err(anyhow!(
-                    "params must be an object but got: \n{}",
-                    serde_yaml::to_string(params)?
-                ));
+    fn check(&self, issues: &mut Issues) {
+        if !matches!(self.url.scheme(), "http" | "https") {
+            issues.at("url", |issues| {
+                issues.error(format!(
+                    "URL scheme must be http or https but got `{}`",
+                    self.url.scheme()
+                ))
+            });
+        }
+        if let Some(ref params) = self.params
+            && !params.is_object()
+        {
+            issues.at("params", |issues| issues.error("params must be an object"));
+        }
+        let bodies: Vec<&str> = [
+            ("body", self.body.is_some()),
+            ("body_raw", self.body_raw.is_some()),
+            ("body_file", self.body_file.is_some()),
+            ("body_base64", self.body_base64.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(key, set)| set.then_some(key))
+        .collect();
+        for key in bodies.iter().skip(1) {
+            issues.at(key, |issues| {
+                issues.error(format!(
+                    "conflicts with `{}`, only one body can be set",
+                    bodies[0]
+                ))
+            });
+        }
+        if let Some(ref path) = self.body_file
+            && !Path::new(path).is_file()
+        {
+            issues.at("body_file", |issues| {
+                issues.warning(format!("file `{}` not found", path))
+            });
+        }
+        if let Some(ref data) = self.body_base64
+            && let Err(e) = decode_base64(data)
+        {
+            issues.at("body_base64", |issues| issues.error(e));
+        }
+        if self.has_body() {
+            match self.method {
+                Method::TRACE => issues.at("body", |issues| {
+                    issues.error("TRACE requests must not have a body")
+                }),
+                Method::GET | Method::HEAD | Method::DELETE | Method::OPTIONS => {
+                    issues.at("body", |issues| {
+                        issues.warning(format!(
+                            "{} requests usually have no body, servers may ignore or reject it",
+                            self.method
+                        ))
+                    })
+                }
+                _ => {}
             }
         }
-        if let Some(ref body) = self.body {
-            if !body.is_object() {
-                return Err(anyhow!(
-                    "body must be an object but got: \n{}",
-                    serde_yaml::to_string(body)?
-                ));
+        issues.at("headers", |issues| {
+            for (name, value) in &self.headers {
+                issues.at(name, |issues| match value.to_str() {
+                    Ok(text) if text.trim() != text => {
+                        issues.warning("value has leading or trailing whitespace, which is dropped")
+                    }
+                    Ok(_) => {}
+                    Err(_) => issues.warning("value is not visible ASCII, servers may reject it"),
+                });
             }
+        });
+        if let Some(ref body) = self.body {
+            issues.at("body", |issues| {
+                if let Some(
+                    content_type @ ("application/x-www-form-urlencoded" | "multipart/form-data"),
+                ) = get_content_type(&self.headers).as_deref()
+                    && !body.is_object()
+                {
+                    issues.error(format!("a {} body must be an object", content_type));
+                }
+                multipart::check_files(body, issues)
+            });
         }
-
-        Ok(())
+        if let Some(ref auth) = self.auth {
+            issues.at("auth", |issues| {
+                if self.headers.contains_key(header::AUTHORIZATION) {
+                    issues.warning("the `authorization` header is sent instead");
+                }
+                auth.check(issues);
+            });
+        }
+        issues.at("retry", |issues| self.retry.check(issues));
     }
 }
 
 impl ResponseExt {
     pub fn get_inner(self) -> Response {
-        self.0
+        self.resp
+    }
+
+    /// Get a message for every failed attempt that was retried.
+    pub fn retries(&self) -> &[String] {
+        &self.retries
     }
 
     pub async fn filter_text(self, profile: &ResponseProfile) -> Result<String> {
-        let resp = self.0;
-        let mut output = get_status_text(&resp)?;
-        write!(
-            &mut output,
-            "{}",
-            get_headers_text(&resp, &profile.skip_headers)?
-        )?;
-        write!(
-            &mut output,
-            "{}",
-            get_body_text(resp, &profile.skip_body).await?
-        )?;
+        let filtered = self.filter(profile).await?;
+        let mut output = filtered.status.clone();
+        write!(&mut output, "{}", filtered.headers)?;
+        write!(&mut output, "{}", filtered.body_text()?)?;
 
         Ok(output)
     }
 
+    /// Split the response into status and headers text, and the body as a filtered JSON value.
+    /// Non-JSON bodies are kept as a JSON string.
+    pub async fn filter(self, profile: &ResponseProfile) -> Result<FilteredResponse> {
+        let resp = self.resp;
+        let status = get_status_text(&resp)?;
+        let headers = filter_headers_text(&resp, profile)?;
+        let body = filter_body(resp, profile).await?;
+
+        Ok(FilteredResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
     pub fn get_header_keys(&self) -> Vec<String> {
-        self.0
+        self.resp
             .headers()
             .iter()
             .map(|(k, _)| k.to_string())
@@ -254,30 +770,119 @@ pub fn get_headers_text(resp: &Response, skip_headers: &[String]) -> Result<Stri
     Ok(text)
 }
 
+/// Get headers text without skipped headers and with normalized values.
+fn filter_headers_text(resp: &Response, profile: &ResponseProfile) -> Result<String> {
+    let mut text = String::new();
+    for (k, v) in resp.headers() {
+        if profile.skip_headers.contains(&k.to_string()) {
+            continue;
+        }
+        let normalized = match v.to_str() {
+            Ok(s) => profile.normalize_header(k.as_str(), s)?,
+            Err(_) => None,
+        };
+        match normalized {
+            Some(v) => writeln!(&mut text, "{}: {:?}", k, v)?,
+            None => writeln!(&mut text, "{}: {:?}", k, v)?,
+        }
+    }
+    writeln!(&mut text)?;
+
+    Ok(text)
+}
+
+/// Get the body without skipped nodes and with normalized values.
+/// Non-JSON bodies are kept as a JSON string.
+async fn filter_body(resp: Response, profile: &ResponseProfile) -> Result<serde_json::Value> {
+    let mut json = get_body_json(resp, &profile.skip_body).await?;
+    profile.normalize_json(&mut json)?;
+    Ok(json)
+}
+
 pub async fn get_body_text(resp: Response, skip_body: &[String]) -> Result<String> {
     let content_type = get_content_type(resp.headers());
     let text = resp.text().await?;
     match content_type.as_deref() {
-        Some("application/json") => filter_json(&text, skip_body),
+        Some(content_type) if is_json(content_type) => filter_json(&text, skip_body),
         _ => Ok(text),
     }
 }
 
-fn filter_json(text: &str, skip: &[String]) -> Result<String> {
-    let mut json: serde_json::Value = serde_json::from_str(text)?;
-    if let serde_json::Value::Object(ref mut obj) = json {
-        for k in skip {
-            obj.remove(k);
+pub async fn get_body_json(resp: Response, skip_body: &[String]) -> Result<serde_json::Value> {
+    let content_type = get_content_type(resp.headers());
+    let text = resp.text().await?;
+    match content_type.as_deref() {
+        Some(content_type) if is_json(content_type) => {
+            let mut json = serde_json::from_str(&text)?;
+            skip_json(&mut json, skip_body)?;
+            Ok(json)
         }
+        _ => Ok(serde_json::Value::String(text)),
     }
+}
+
+fn filter_json(text: &str, skip: &[String]) -> Result<String> {
+    let mut json: serde_json::Value = serde_json::from_str(text)?;
+    skip_json(&mut json, skip)?;
     Ok(serde_json::to_string_pretty(&json)?)
 }
 
+/// Remove every node matched by the `skip` paths from `json`.
+fn skip_json(json: &mut serde_json::Value, skip: &[String]) -> Result<()> {
+    for path in skip {
+        path.parse::<JsonPath>()?.remove(json);
+    }
+    Ok(())
+}
+
+/// Get the media type of the `content-type` header, lowercased and without parameters.
 fn get_content_type(headers: &HeaderMap) -> Option<String> {
     headers
         .get(header::CONTENT_TYPE)
-        .and_then(|v| v.to_str().unwrap().split(";").next())
-        .map(|v| v.to_string())
+        .and_then(|v| v.to_str().ok()?.split(";").next())
+        .map(|v| v.trim().to_ascii_lowercase())
+}
+
+/// Attach the failed attempts which were retried to an error, as they are lost otherwise.
+fn with_retries<T>(result: Result<T>, retries: &[String]) -> Result<T> {
+    match retries {
+        [] => result,
+        _ => result.with_context(|| retries.join("\n")),
+    }
+}
+
+/// Whether a content type is JSON, like `application/json` or `application/ld+json`.
+fn is_json(content_type: &str) -> bool {
+    content_type == "application/json" || content_type.ends_with("+json")
+}
+
+/// Encode a body as newline delimited JSON, one line per item of an array.
+fn ndjson(body: &serde_json::Value) -> Result<String> {
+    let items = match body {
+        serde_json::Value::Array(items) => items.iter().collect(),
+        body => vec![body],
+    };
+    let mut text = String::new();
+    for item in items {
+        writeln!(&mut text, "{}", serde_json::to_string(item)?)?;
+    }
+    Ok(text)
+}
+
+/// Decode a base64 body, ignoring the line breaks of long values.
+fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    let data: String = data.split_whitespace().collect();
+    BASE64_STANDARD
+        .decode(data)
+        .map_err(|e| anyhow!("invalid base64: {}", e))
+}
+
+/// Show a body as text, or its size if it is binary.
+fn bytes_value(bytes: Vec<u8>) -> serde_json::Value {
+    match String::from_utf8(bytes) {
+        Ok(text) => text.into(),
+        Err(e) => format!("<{} bytes of binary data>", e.as_bytes().len()).into(),
+    }
 }
 
 /// Check if the JSON value is null or empty object.
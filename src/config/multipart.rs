@@ -0,0 +1,160 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::Issues;
+
+/// Key marking a body field as a file part.
+const FILE: &str = "@file";
+
+/// A file sent as a part of a multipart form, written as `{"@file": path}` in a body.
+/// Paths are relative to the config file, or to the current directory when given with `-e`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilePart {
+    #[serde(rename = "@file")]
+    pub path: String,
+    /// File name sent with the part, defaults to the name of the file.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub filename: Option<String>,
+    /// Content type of the part, guessed from the file extension by default.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub content_type: Option<String>,
+}
+
+impl FromStr for FilePart {
+    type Err = anyhow::Error;
+
+    /// Parse a file given on the command line, e.g. `./avatar.png;type=image/png;filename=me.png`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut options = s.split(';');
+        let path = options.next().unwrap_or_default().trim();
+        if path.is_empty() {
+            return Err(anyhow!("missing file path in `{}`", s));
+        }
+
+        let mut part = FilePart {
+            path: path.to_string(),
+            filename: None,
+            content_type: None,
+        };
+        for option in options {
+            match option.split_once('=') {
+                Some(("type", content_type)) => part.content_type = Some(content_type.to_string()),
+                Some(("filename", filename)) => part.filename = Some(filename.to_string()),
+                _ => {
+                    return Err(anyhow!(
+                        "invalid file option `{}`, expected `type=` or `filename=`",
+                        option
+                    ));
+                }
+            }
+        }
+        Ok(part)
+    }
+}
+
+impl FilePart {
+    /// Get the file part of a body field, if it is one.
+    pub fn from_json(value: &Value) -> Result<Option<Self>> {
+        match value {
+            Value::Object(fields) if fields.contains_key(FILE) => {
+                Ok(Some(serde_json::from_value(value.clone())?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn part(&self) -> Result<Part> {
+        let path = Path::new(&self.path);
+        let mut part = Part::file(path)
+            .await
+            .with_context(|| format!("failed to read file {}", path.display()))?;
+        if let Some(filename) = &self.filename {
+            part = part.file_name(filename.clone());
+        }
+        if let Some(content_type) = &self.content_type {
+            part = part.mime_str(content_type)?;
+        }
+        Ok(part)
+    }
+}
+
+/// Whether any field of a body is a file.
+pub fn has_files(body: &Value) -> bool {
+    let Value::Object(fields) = body else {
+        return false;
+    };
+    fields.values().any(|value| match value {
+        Value::Array(items) => items.iter().any(is_file),
+        value => is_file(value),
+    })
+}
+
+fn is_file(value: &Value) -> bool {
+    value.as_object().is_some_and(|v| v.contains_key(FILE))
+}
+
+/// Build a multipart form from a body object. Arrays send one part per item,
+/// other objects are sent as JSON.
+pub async fn form(body: &Value) -> Result<Form> {
+    let Value::Object(fields) = body else {
+        return Err(anyhow!("multipart body must be an object"));
+    };
+
+    let mut form = Form::new();
+    for (name, value) in fields {
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let part = field_part(value)
+                .await
+                .with_context(|| format!("invalid multipart field `{}`", name))?;
+            form = form.part(name.clone(), part);
+        }
+    }
+    Ok(form)
+}
+
+async fn field_part(value: &Value) -> Result<Part> {
+    if let Some(file) = FilePart::from_json(value)? {
+        return file.part().await;
+    }
+    match value {
+        Value::String(text) => Ok(Part::text(text.clone())),
+        Value::Null => Ok(Part::text("")),
+        Value::Bool(_) | Value::Number(_) => Ok(Part::text(value.to_string())),
+        Value::Array(_) | Value::Object(_) => {
+            Ok(Part::text(value.to_string()).mime_str("application/json")?)
+        }
+    }
+}
+
+/// Check the file parts of a body, warning about files which don't exist (yet).
+pub fn check_files(body: &Value, issues: &mut Issues) {
+    let Value::Object(fields) = body else {
+        return;
+    };
+    for (name, value) in fields {
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        issues.at(name, |issues| {
+            for value in values {
+                match FilePart::from_json(value) {
+                    Ok(Some(file)) if !Path::new(&file.path).is_file() => {
+                        issues.warning(format!("file `{}` not found", file.path))
+                    }
+                    Ok(_) => {}
+                    Err(e) => issues.error(format!("invalid file part: {}", e)),
+                }
+            }
+        });
+    }
+}
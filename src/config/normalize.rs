@@ -0,0 +1,368 @@
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use serde_json::Value;
+use std::sync::{LazyLock, OnceLock};
+
+use super::{Issues, Validateable};
+use crate::JsonPath;
+
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        // ISO 8601 / RFC 3339, e.g. 2025-04-01T12:30:00.123Z
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?",
+        // RFC 2822 / HTTP date, e.g. Tue, 01 Apr 2025 12:30:00 GMT
+        r"|(Mon|Tue|Wed|Thu|Fri|Sat|Sun), \d{1,2} \w{3} \d{4} \d{2}:\d{2}:\d{2} (GMT|[+-]\d{4})",
+    ))
+    .unwrap()
+});
+
+/// Rewrite body and header values before diffing, so a field is still
+/// compared for presence and shape while its volatile value is masked.
+#[derive(Debug, Serialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct NormalizeRule {
+    /// JSON paths of body values to rewrite.
+    /// Containers are rewritten recursively.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub body: Vec<String>,
+    /// Names of headers to rewrite.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub headers: Vec<String>,
+    #[serde(flatten)]
+    pub action: NormalizeAction,
+    #[serde(skip)]
+    #[schemars(skip)]
+    compiled: Compiled,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "lowercase", deny_unknown_fields)]
+pub enum NormalizeAction {
+    /// Replace every match of `pattern` with `with`, e.g. UUIDs with `<uuid>`.
+    Replace { pattern: String, with: String },
+    /// Replace timestamps (RFC 3339, HTTP dates and unix epochs) with `with`.
+    Timestamp {
+        #[serde(default = "default_timestamp")]
+        with: String,
+    },
+    /// Round numbers to `digits` decimal places.
+    Round { digits: u32 },
+    /// Trim leading and trailing whitespace.
+    Trim,
+    /// Lowercase strings.
+    Lowercase,
+}
+
+/// Paths and pattern of a rule, compiled once instead of for every response.
+#[derive(Debug, Clone, Default)]
+struct Compiled(OnceLock<Result<Matchers, String>>);
+
+#[derive(Debug, Clone)]
+struct Matchers {
+    body: Vec<JsonPath>,
+    pattern: Option<Regex>,
+}
+
+/// Compiled matchers are derived from the fields of a rule, so rules compare by their fields.
+impl PartialEq for Compiled {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Compiled {}
+
+fn default_timestamp() -> String {
+    "<ts>".to_string()
+}
+
+/// The action is flattened into the rule, which `deny_unknown_fields` can't check,
+/// so keys other than `body` and `headers` are left to the action to reject.
+impl<'de> Deserialize<'de> for NormalizeRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Rule {
+            #[serde(default)]
+            body: Vec<String>,
+            #[serde(default)]
+            headers: Vec<String>,
+            #[serde(flatten)]
+            action: serde_json::Map<String, Value>,
+        }
+
+        let rule = Rule::deserialize(deserializer)?;
+        let action = NormalizeAction::deserialize(Value::Object(rule.action.clone()))
+            .map_err(D::Error::custom)?;
+        // Unit variants such as `trim` ignore any other key.
+        let known = serde_json::to_value(&action).map_err(D::Error::custom)?;
+        if let Some(key) = rule.action.keys().find(|key| known.get(key).is_none()) {
+            return Err(D::Error::unknown_field(key, &["body", "headers", "action"]));
+        }
+        Ok(Self::new(rule.body, rule.headers, action))
+    }
+}
+
+impl NormalizeRule {
+    pub fn new(body: Vec<String>, headers: Vec<String>, action: NormalizeAction) -> Self {
+        Self {
+            body,
+            headers,
+            action,
+            compiled: Compiled::default(),
+        }
+    }
+
+    /// Compile the paths and pattern of the rule, which is otherwise done on first use.
+    pub fn compile(&self) -> Result<()> {
+        self.matchers().map(|_| ())
+    }
+
+    fn matchers(&self) -> Result<&Matchers> {
+        let compiled = self.compiled.0.get_or_init(|| {
+            let body = self
+                .body
+                .iter()
+                .map(|path| path.parse())
+                .collect::<Result<_>>()
+                .map_err(|e| format!("{:#}", e))?;
+            let pattern = match &self.action {
+                NormalizeAction::Replace { pattern, .. } => {
+                    Some(Regex::new(pattern).map_err(|e| e.to_string())?)
+                }
+                _ => None,
+            };
+            Ok(Matchers { body, pattern })
+        });
+        compiled.as_ref().map_err(|e| anyhow!("{}", e))
+    }
+
+    /// Apply the rule to every body node matched by `body` paths.
+    pub fn apply_json(&self, json: &mut Value) -> Result<()> {
+        for path in &self.matchers()?.body {
+            let mut result = Ok(());
+            path.for_each_mut(json, &mut |v| {
+                if result.is_ok() {
+                    result = self.apply_value(v);
+                }
+            });
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Apply the rule to a header value if the header name is selected.
+    pub fn apply_header(&self, name: &str, value: &str) -> Result<Option<String>> {
+        if !self.headers.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+            return Ok(None);
+        }
+        let mut v = Value::String(value.to_string());
+        self.apply_value(&mut v)?;
+        Ok(Some(match v {
+            Value::String(s) => s,
+            v => v.to_string(),
+        }))
+    }
+
+    fn apply_value(&self, v: &mut Value) -> Result<()> {
+        match v {
+            Value::Object(obj) => obj.values_mut().try_for_each(|v| self.apply_value(v)),
+            Value::Array(arr) => arr.iter_mut().try_for_each(|v| self.apply_value(v)),
+            Value::String(s) => {
+                if let Some(new) = self.apply_str(s)? {
+                    *v = new;
+                }
+                Ok(())
+            }
+            Value::Number(n) => {
+                match &self.action {
+                    NormalizeAction::Round { digits } => {
+                        if let Some(f) = n.as_f64().filter(|_| !n.is_i64() && !n.is_u64()) {
+                            let factor = 10f64.powi(*digits as i32);
+                            *v = Value::from((f * factor).round() / factor);
+                        }
+                    }
+                    NormalizeAction::Timestamp { with } if is_epoch(n) => {
+                        *v = Value::String(with.clone());
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+            Value::Bool(_) | Value::Null => Ok(()),
+        }
+    }
+
+    fn apply_str(&self, s: &str) -> Result<Option<Value>> {
+        let new = match &self.action {
+            NormalizeAction::Replace { with, .. } => match &self.matchers()?.pattern {
+                Some(pattern) => pattern.replace_all(s, with.as_str()).into_owned(),
+                None => return Ok(None),
+            },
+            NormalizeAction::Timestamp { with } => {
+                TIMESTAMP.replace_all(s, with.as_str()).into_owned()
+            }
+            NormalizeAction::Round { digits } => match s.trim().parse::<f64>() {
+                Ok(f) if s.contains('.') => {
+                    format!("{:.*}", *digits as usize, f)
+                }
+                _ => return Ok(None),
+            },
+            NormalizeAction::Trim => s.trim().to_string(),
+            NormalizeAction::Lowercase => s.to_lowercase(),
+        };
+        Ok(Some(Value::String(new)))
+    }
+}
+
+/// Whether a number looks like a unix timestamp in seconds or milliseconds.
+fn is_epoch(n: &serde_json::Number) -> bool {
+    n.as_u64().is_some_and(|n| {
+        (1_000_000_000..10_000_000_000).contains(&n)
+            || (1_000_000_000_000..10_000_000_000_000).contains(&n)
+    })
+}
+
+impl Validateable for NormalizeRule {
+    fn check(&self, issues: &mut Issues) {
+        if self.body.is_empty() && self.headers.is_empty() {
+            issues.error("normalize rule must select body paths or headers");
+        }
+        for (index, path) in self.body.iter().enumerate() {
+            if let Err(e) = path.parse::<JsonPath>() {
+                issues.at("body", |issues| {
+                    issues.at_index(index, |issues| {
+                        issues.error(format!("invalid JSON path: {:#}", e))
+                    })
+                });
+            }
+        }
+        if let NormalizeAction::Replace { pattern, .. } = &self.action
+            && let Err(e) = Regex::new(pattern)
+        {
+            issues.at("pattern", |issues| {
+                issues.error(format!("invalid regex: {}", e))
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(yaml: &str) -> Result<NormalizeRule, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    #[test]
+    fn apply_json_rewrites_selected_values_for_each_action() {
+        let cases = [
+            (
+                "{body: [id], action: replace, pattern: '[0-9a-f]{8}', with: <uuid>}",
+                json!({"id": "id-1234abcd", "other": "1234abcd"}),
+                json!({"id": "id-<uuid>", "other": "1234abcd"}),
+            ),
+            (
+                "{body: [at, 'items[*].at'], action: timestamp}",
+                json!({"at": "at 2025-04-01T12:30:00.123Z", "items": [{"at": 1743510600}]}),
+                json!({"at": "at <ts>", "items": [{"at": "<ts>"}]}),
+            ),
+            (
+                "{body: [date, ms, small], action: timestamp, with: T}",
+                json!({"date": "Tue, 01 Apr 2025 12:30:00 GMT", "ms": 1743510600000u64, "small": 42}),
+                json!({"date": "T", "ms": "T", "small": 42}),
+            ),
+            (
+                "{body: [n], action: round, digits: 2}",
+                json!({"n": {"f": 1.23456, "i": 7, "s": "2.5", "t": "x"}}),
+                json!({"n": {"f": 1.23, "i": 7, "s": "2.50", "t": "x"}}),
+            ),
+            (
+                "{body: ['$[*]'], action: trim}",
+                json!([" a ", {"b": "\tb\n"}, 1, true, null]),
+                json!(["a", {"b": "b"}, 1, true, null]),
+            ),
+            (
+                "{body: [..name], action: lowercase}",
+                json!({"name": "A", "nested": {"name": "MiXed"}, "other": "B"}),
+                json!({"name": "a", "nested": {"name": "mixed"}, "other": "B"}),
+            ),
+        ];
+        for (yaml, mut json, expected) in cases {
+            rule(yaml).unwrap().apply_json(&mut json).unwrap();
+            assert_eq!(json, expected, "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn apply_header_rewrites_selected_headers_only() {
+        let rule =
+            rule("{headers: [X-Request-Id], action: replace, pattern: '\\d+', with: N}").unwrap();
+        assert_eq!(
+            rule.apply_header("x-request-id", "req-42").unwrap(),
+            Some("req-N".into())
+        );
+        assert_eq!(rule.apply_header("date", "42").unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let cases = [
+            ("{paths: [a], action: trim}", "unknown field `paths`"),
+            (
+                "{body: [a], action: replace, pattern: x, replace_with: y}",
+                "unknown field `replace_with`",
+            ),
+            (
+                "{body: [a], action: round, digits: 1, places: 2}",
+                "unknown field `places`",
+            ),
+            ("{body: [a], action: squash}", "unknown variant `squash`"),
+            ("{body: [a], action: round}", "missing field `digits`"),
+        ];
+        for (yaml, expected) in cases {
+            let err = rule(yaml).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}: {}", yaml, err);
+        }
+    }
+
+    #[test]
+    fn check_reports_invalid_paths_and_patterns() {
+        let cases = [
+            ("{body: [a], action: trim}", vec![]),
+            (
+                "{action: trim}",
+                vec!["normalize rule must select body paths or headers"],
+            ),
+            (
+                "{body: [a, 'b['], action: trim}",
+                vec!["body[1]: invalid JSON path"],
+            ),
+            (
+                "{headers: [a], action: replace, pattern: '[a-', with: x}",
+                vec!["pattern: invalid regex"],
+            ),
+        ];
+        for (yaml, expected) in cases {
+            let mut issues = Issues::default();
+            rule(yaml).unwrap().check(&mut issues);
+            let issues = issues
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            for expected in &expected {
+                assert!(issues.contains(expected), "{}: {}", yaml, issues);
+            }
+            assert_eq!(
+                issues.is_empty(),
+                expected.is_empty(),
+                "{}: {}",
+                yaml,
+                issues
+            );
+        }
+    }
+}
@@ -1,30 +1,112 @@
-use super::{LoadConfig, RequestProfile, Validateable, is_default};
-use anyhow::{Context, Result};
+use super::inherit::merge;
+use super::{
+    ClientConfig, Issues, LoadConfig, NormalizeRule, RequestProfile, Validateable, is_default,
+    schema, with_retries,
+};
+use anyhow::{Context, Result, anyhow};
+use glob::Pattern;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::{sync::Semaphore, task::JoinSet};
 
-use crate::{ExtraArgs, diff_text};
+use crate::{DiffResult, ExtraArgs, JsonPath, ProfileRun, SectionDiff, Side, diff_json};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct DiffConfig {
+    /// HTTP client settings shared by all profiles.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    /// Template variables shared by all profiles.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    #[schemars(schema_with = "schema::vars")]
+    pub vars: HashMap<String, String>,
     #[serde(flatten)]
     pub profiles: HashMap<String, DiffProfile>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A profile is either given as two full requests in `req1` and `req2`,
+/// or as one `request` sent to both `targets`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(try_from = "DiffProfileDef")]
 pub struct DiffProfile {
     pub req1: RequestProfile,
     pub req2: RequestProfile,
     #[serde(skip_serializing_if = "is_default", default)]
     pub resp: ResponseProfile,
+    /// Tags used to select profiles for batch runs.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+    /// HTTP client settings for both requests.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    /// Template variables for both requests, e.g. `host` used as `{{host}}/api/users`.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    #[schemars(schema_with = "schema::vars")]
+    pub vars: HashMap<String, String>,
+}
+
+/// Both forms of a profile as written in a config.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct DiffProfileDef {
+    req1: Option<RequestProfile>,
+    req2: Option<RequestProfile>,
+    /// Request sent to both targets, where `url` is the path below the target.
+    #[serde(default)]
+    #[schemars(with = "Option<RequestProfile>")]
+    request: Option<Mapping>,
+    #[serde(default)]
+    targets: Vec<Target>,
+    #[serde(default)]
+    resp: ResponseProfile,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    client: ClientConfig,
+    #[serde(default)]
+    #[schemars(schema_with = "schema::vars")]
+    vars: HashMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+/// Base URL of a target, optionally with headers, params or other request
+/// fields overriding the shared `request` for this side only.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum Target {
+    Url(String),
+    Overrides(#[schemars(with = "RequestProfile")] Mapping),
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
 pub struct ResponseProfile {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_headers: Vec<String>,
+    /// JSON paths of body nodes to drop before diffing,
+    /// e.g. `id`, `data.meta.request_id`, `items[*].updated_at` or `$..trace_id`.
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_body: Vec<String>,
+    /// Rules rewriting body and header values before diffing.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub normalize: Vec<NormalizeRule>,
+    /// How response bodies are compared.
+    /// Defaults to a line diff of the pretty-printed text.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub diff_mode: DiffMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    /// Line diff of the pretty-printed body.
+    #[default]
+    Text,
+    /// Structural diff of the JSON body, reported by JSON path.
+    Json,
 }
 
 impl ResponseProfile {
@@ -32,54 +114,444 @@ impl ResponseProfile {
         Self {
             skip_headers,
             skip_body,
+            normalize: vec![],
+            diff_mode: DiffMode::default(),
         }
     }
+
+    /// Normalize body values with every rule in order.
+    pub fn normalize_json(&self, json: &mut serde_json::Value) -> Result<()> {
+        for rule in &self.normalize {
+            rule.apply_json(json)?;
+        }
+        Ok(())
+    }
+
+    /// Normalize a header value, returning `None` if no rule selects the header.
+    pub fn normalize_header(&self, name: &str, value: &str) -> Result<Option<String>> {
+        let mut result = None;
+        for rule in &self.normalize {
+            let current = result.as_deref().unwrap_or(value);
+            if let Some(v) = rule.apply_header(name, current)? {
+                result = Some(v);
+            }
+        }
+        Ok(result)
+    }
 }
 
-impl LoadConfig for DiffConfig {}
+impl LoadConfig for DiffConfig {
+    const FILE_NAME: &'static str = "rdiff.yaml";
+    const ENV_VAR: &'static str = "RDIFF_CONFIG";
+    type Profile = DiffProfile;
+
+    fn resolve(&mut self) -> Result<()> {
+        for profile in self.profiles.values_mut() {
+            let client = self.client.merge(&profile.client);
+            profile.req1.client = client.merge(&profile.req1.client);
+            profile.req2.client = client.merge(&profile.req2.client);
+            // Invalid rules are reported by `check`.
+            for rule in &profile.resp.normalize {
+                let _ = rule.compile();
+            }
+        }
+        Ok(())
+    }
+}
 
 impl DiffConfig {
     pub fn new(profiles: HashMap<String, DiffProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            vars: HashMap::new(),
+            profiles,
+        }
     }
 
     pub fn get_profile(&self, name: &str) -> Option<&DiffProfile> {
         self.profiles.get(name)
     }
+
+    /// Select profile names matching any of the glob `patterns` or having any of the `tags`.
+    /// Returns all profiles if both are empty. Names are sorted.
+    pub fn select(&self, patterns: &[String], tags: &[String]) -> Result<Vec<String>> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::new(p).context(format!("invalid profile pattern: {}", p)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut names: Vec<String> = self
+            .profiles
+            .iter()
+            .filter(|(name, profile)| {
+                (patterns.is_empty() && tags.is_empty())
+                    || patterns.iter().any(|p| p.matches(name))
+                    || profile.tags.iter().any(|t| tags.contains(t))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Diff the named profiles concurrently, running at most `concurrency` at a time.
+    /// Results are returned in the order of `names`.
+    pub async fn diff_all(
+        &self,
+        names: &[String],
+        args: &ExtraArgs,
+        concurrency: usize,
+    ) -> Result<Vec<ProfileRun>> {
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut set = JoinSet::new();
+        for (idx, name) in names.iter().enumerate() {
+            let profile = self
+                .get_profile(name)
+                .ok_or_else(|| anyhow!("Profile {} not found", name))?
+                .clone();
+            let (name, args, semaphore) = (name.clone(), args.clone(), semaphore.clone());
+            set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let start = Instant::now();
+                let result = profile.diff(args).await;
+                (idx, ProfileRun::new(name, start.elapsed(), result))
+            });
+        }
+
+        let mut runs = set.join_all().await;
+        runs.sort_by_key(|(idx, _)| *idx);
+        Ok(runs.into_iter().map(|(_, run)| run).collect())
+    }
 }
 
 impl DiffProfile {
     pub fn new(req1: RequestProfile, req2: RequestProfile, resp: ResponseProfile) -> Self {
-        Self { req1, req2, resp }
+        Self {
+            req1,
+            req2,
+            resp,
+            tags: vec![],
+            client: ClientConfig::default(),
+            vars: HashMap::new(),
+        }
+    }
+
+    pub async fn diff(&self, args: ExtraArgs) -> Result<DiffResult> {
+        let resp1 = self.req1.send(&args.side(Side::Req1)).await?;
+        let resp2 = with_retries(
+            self.req2.send(&args.side(Side::Req2)).await,
+            resp1.retries(),
+        )?;
+        let retries = [resp1.retries(), resp2.retries()].concat();
+
+        let resp1 = resp1.filter(&self.resp).await?;
+        let resp2 = resp2.filter(&self.resp).await?;
+
+        let changes = match self.resp.diff_mode {
+            DiffMode::Text => None,
+            DiffMode::Json => Some(diff_json(&resp1.body, &resp2.body)),
+        };
+
+        let body = SectionDiff::new(resp1.body_text()?, resp2.body_text()?);
+        let mut result = DiffResult::new(
+            SectionDiff::new(format!("{}\n", resp1.status), format!("{}\n", resp2.status)),
+            SectionDiff::new(resp1.headers, resp2.headers),
+            body,
+            changes,
+        );
+        result.retries = retries;
+        Ok(result)
+    }
+}
+
+impl TryFrom<DiffProfileDef> for DiffProfile {
+    type Error = anyhow::Error;
+
+    fn try_from(def: DiffProfileDef) -> Result<Self> {
+        let (req1, req2) = match (def.req1, def.req2, def.request) {
+            (Some(req1), Some(req2), None) if def.targets.is_empty() => (req1, req2),
+            (None, None, Some(request)) => {
+                let [target1, target2]: [Target; 2] =
+                    def.targets.try_into().map_err(|targets: Vec<Target>| {
+                        anyhow!(
+                            "`targets` must have exactly 2 entries but got {}",
+                            targets.len()
+                        )
+                    })?;
+                (target1.request(&request)?, target2.request(&request)?)
+            }
+            (None, None, None) => {
+                return Err(anyhow!(
+                    "profile needs either `req1` and `req2`, or `request` and `targets`"
+                ));
+            }
+            (Some(_), Some(_), None) => return Err(anyhow!("`targets` need a shared `request`")),
+            (_, _, None) => return Err(anyhow!("profile needs both `req1` and `req2`")),
+            _ => {
+                return Err(anyhow!(
+                    "`req1` and `req2` can't be combined with `request` and `targets`"
+                ));
+            }
+        };
+
+        Ok(Self {
+            req1,
+            req2,
+            resp: def.resp,
+            tags: def.tags,
+            client: def.client,
+            vars: def.vars,
+        })
     }
+}
 
-    pub async fn diff(&self, args: ExtraArgs) -> Result<String> {
-        let resp1 = self.req1.send(&args).await?;
-        let resp2 = self.req2.send(&args).await?;
+impl Target {
+    /// Build the request for this target from the shared `request`.
+    fn request(self, request: &Mapping) -> Result<RequestProfile> {
+        let (base, overrides) = match self {
+            Target::Url(base) => (base, Mapping::new()),
+            Target::Overrides(mut overrides) => match overrides.remove("url") {
+                Some(Value::String(base)) => (base, overrides),
+                _ => return Err(anyhow!("target must have a base `url`")),
+            },
+        };
 
-        let text1 = resp1.filter_text(&self.resp).await?;
-        let text2 = resp2.filter_text(&self.resp).await?;
+        let mut request = request.clone();
+        merge(&mut request, overrides);
+        let url = match request.get("url") {
+            Some(Value::String(path)) => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            ),
+            Some(_) => return Err(anyhow!("request `url` must be a path string")),
+            None => base,
+        };
+        request.insert("url".into(), url.into());
 
-        diff_text(&text1, &text2)
+        Ok(serde_yaml::from_value(Value::Mapping(request))?)
     }
 }
 
 impl Validateable for DiffConfig {
-    fn validate(&self) -> Result<()> {
-        for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("failed to validate profile: {}", name))?;
+    fn check(&self, issues: &mut Issues) {
+        let mut names: Vec<&String> = self.profiles.keys().collect();
+        names.sort();
+        for name in names {
+            issues.at(name, |issues| self.profiles[name].check(issues));
         }
-
-        Ok(())
     }
 }
 
 impl Validateable for DiffProfile {
-    fn validate(&self) -> Result<()> {
-        self.req1.validate().context("req1 validate failed")?;
-        self.req2.validate().context("req2 validate failed")?;
-        Ok(())
+    fn check(&self, issues: &mut Issues) {
+        issues.at("req1", |issues| self.req1.check(issues));
+        issues.at("req2", |issues| self.req2.check(issues));
+        issues.at("resp", |issues| self.resp.check(issues));
+    }
+}
+
+impl Validateable for ResponseProfile {
+    fn check(&self, issues: &mut Issues) {
+        for (index, path) in self.skip_body.iter().enumerate() {
+            if let Err(e) = path.parse::<JsonPath>() {
+                issues.at("skip_body", |issues| {
+                    issues.at_index(index, |issues| {
+                        issues.error(format!("invalid JSON path: {:#}", e))
+                    })
+                });
+            }
+        }
+        for (index, rule) in self.normalize.iter().enumerate() {
+            issues.at("normalize", |issues| {
+                issues.at_index(index, |issues| rule.check(issues))
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+defaults:
+  req1: {url: "http://a.test"}
+  req2: {url: "http://b.test"}
+users.list: {tags: [smoke, users]}
+users.get: {tags: [users]}
+orders.list: {tags: [smoke]}
+health: {}
+"#;
+
+    #[test]
+    fn select_matches_globs_or_tags() {
+        let config = DiffConfig::from_yaml(CONFIG).unwrap();
+        let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let cases: [(&[&str], &[&str], &[&str]); 8] = [
+            (
+                &[],
+                &[],
+                &["health", "orders.list", "users.get", "users.list"],
+            ),
+            (&["users.*"], &[], &["users.get", "users.list"]),
+            (
+                &["*.list", "health"],
+                &[],
+                &["health", "orders.list", "users.list"],
+            ),
+            (&[], &["smoke"], &["orders.list", "users.list"]),
+            (
+                &[],
+                &["smoke", "users"],
+                &["orders.list", "users.get", "users.list"],
+            ),
+            (
+                &["health"],
+                &["smoke"],
+                &["health", "orders.list", "users.list"],
+            ),
+            (&["missing.*"], &[], &[]),
+            (&[], &["missing"], &[]),
+        ];
+        for (patterns, tags, expected) in cases {
+            let names = config.select(&strings(patterns), &strings(tags)).unwrap();
+            assert_eq!(names, strings(expected), "{:?} {:?}", patterns, tags);
+        }
+    }
+
+    #[test]
+    fn null_timeouts_survive_inheritance() {
+        let config = DiffConfig::from_yaml(
+            r#"
+client: {connect_timeout: null}
+defaults:
+  req1: {url: "http://a.test", client: {timeout: 5s, read_timeout: 1s}}
+  req2: {url: "http://b.test", client: {timeout: null}}
+p:
+  req1: {client: {timeout: null}}
+"#,
+        )
+        .unwrap();
+        let profile = config.get_profile("p").unwrap();
+        let (req1, req2) = (&profile.req1.client, &profile.req2.client);
+        assert_eq!(req1.timeout, Some(Duration::ZERO));
+        assert_eq!(req1.read_timeout, Some(Duration::from_secs(1)));
+        assert_eq!(req1.connect_timeout, Some(Duration::ZERO));
+        assert_eq!(req2.timeout, Some(Duration::ZERO));
+    }
+
+    /// Summary of a request: its URL and the values of `headers` and `params`.
+    fn summary(req: &RequestProfile) -> String {
+        let mut headers: Vec<_> = req
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value.to_str().unwrap()))
+            .collect();
+        headers.sort();
+        let params = req
+            .params
+            .as_ref()
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+        format!("{} {} {}", req.url, headers.join(","), params)
+    }
+
+    #[test]
+    fn both_forms_inherit_defaults_written_with_sides() {
+        let config = DiffConfig::from_yaml(
+            r#"
+defaults:
+  req1: {url: "http://a.test", headers: {x-side: one}}
+  req2: {url: "http://b.test", headers: {x-side: two}, params: {p: 2}}
+sides:
+  req1: {url: "http://a.test/users"}
+  req2: {headers: {x-side: own}}
+shared:
+  request: {url: /users, headers: {x-req: r}}
+  targets: ["http://c.test", {url: "http://d.test", headers: {x-side: own}}]
+"#,
+        )
+        .unwrap();
+        let cases = [
+            (
+                "sides",
+                "http://a.test/users x-side=one ",
+                r#"http://b.test/ x-side=own {"p":2}"#,
+            ),
+            (
+                "shared",
+                "http://c.test/users x-req=r,x-side=one ",
+                r#"http://d.test/users x-req=r,x-side=own {"p":2}"#,
+            ),
+        ];
+        for (name, req1, req2) in cases {
+            let profile = config.get_profile(name).unwrap();
+            assert_eq!(summary(&profile.req1), req1, "{}", name);
+            assert_eq!(summary(&profile.req2), req2, "{}", name);
+        }
+    }
+
+    #[test]
+    fn both_forms_inherit_defaults_written_with_a_request() {
+        let config = DiffConfig::from_yaml(
+            r#"
+defaults:
+  request: {url: /users, headers: {x-req: r}, params: {p: 1}}
+  targets: ["http://a.test", "http://b.test"]
+sides:
+  req1: {url: "http://c.test/items"}
+  req2: {url: "http://d.test/items", params: {p: 2}}
+shared:
+  request: {headers: {x-req: own}}
+"#,
+        )
+        .unwrap();
+        let cases = [
+            (
+                "sides",
+                r#"http://c.test/items x-req=r {"p":1}"#,
+                r#"http://d.test/items x-req=r {"p":2}"#,
+            ),
+            (
+                "shared",
+                r#"http://a.test/users x-req=own {"p":1}"#,
+                r#"http://b.test/users x-req=own {"p":1}"#,
+            ),
+        ];
+        for (name, req1, req2) in cases {
+            let profile = config.get_profile(name).unwrap();
+            assert_eq!(summary(&profile.req1), req1, "{}", name);
+            assert_eq!(summary(&profile.req2), req2, "{}", name);
+        }
+    }
+
+    #[test]
+    fn sides_and_a_request_in_one_profile_are_rejected() {
+        let config = r#"
+defaults:
+  req1: {url: "http://a.test"}
+  req2: {url: "http://b.test"}
+p:
+  req1: {url: "http://a.test"}
+  request: {url: /users}
+  targets: ["http://c.test", "http://d.test"]
+"#;
+        let err = DiffConfig::from_yaml(config).unwrap_err();
+        assert!(
+            format!("{:#}", err)
+                .contains("`req1` and `req2` can't be combined with `request` and `targets`"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn select_rejects_invalid_patterns() {
+        let config = DiffConfig::from_yaml(CONFIG).unwrap();
+        let err = config.select(&["[".to_string()], &[]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid profile pattern: [");
     }
 }
@@ -0,0 +1,176 @@
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use super::{Issues, Validateable, duration};
+
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(200);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+const DEFAULT_RETRY_STATUS: [u16; 3] = [502, 503, 504];
+
+/// When and how often a request is retried.
+/// Without `max_attempts` a request is sent only once. Only sending the request
+/// and receiving the response head are retried, not reading the response body.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_attempts: Option<u32>,
+    /// Delay before the first retry, doubled for each further retry.
+    /// Defaults to 200ms.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub backoff: Option<Duration>,
+    /// Upper bound of the delay between attempts.
+    /// Defaults to 10s.
+    #[serde(with = "duration", skip_serializing_if = "Option::is_none", default)]
+    #[schemars(schema_with = "duration::schema")]
+    pub max_backoff: Option<Duration>,
+    /// Randomize each delay between half and the full backoff.
+    /// Defaults to true.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub jitter: Option<bool>,
+    /// Response statuses which are retried.
+    /// Defaults to 502, 503 and 504.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub on_status: Option<Vec<u16>>,
+    /// Retry when the connection fails or times out.
+    /// Defaults to true.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub on_connection_error: Option<bool>,
+}
+
+impl RetryPolicy {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(1).max(1)
+    }
+
+    /// Whether a response with `status` should be retried.
+    pub fn retry_status(&self, status: StatusCode) -> bool {
+        match &self.on_status {
+            Some(statuses) => statuses.contains(&status.as_u16()),
+            None => DEFAULT_RETRY_STATUS.contains(&status.as_u16()),
+        }
+    }
+
+    /// Whether a failed request should be retried.
+    pub fn retry_error(&self, err: &reqwest::Error) -> bool {
+        self.on_connection_error.unwrap_or(true) && (err.is_connect() || err.is_timeout())
+    }
+
+    /// Delay before the attempt following `attempt`, starting at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff.unwrap_or(DEFAULT_BACKOFF);
+        let max = self.max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF);
+        let delay = backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(max);
+
+        if self.jitter.unwrap_or(true) {
+            let random = RandomState::new().build_hasher().finish();
+            delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+        } else {
+            delay
+        }
+    }
+}
+
+impl Validateable for RetryPolicy {
+    fn check(&self, issues: &mut Issues) {
+        if self.max_attempts == Some(0) {
+            issues.at("max_attempts", |issues| issues.error("must be at least 1"));
+        }
+        for (index, status) in self.on_status.iter().flatten().enumerate() {
+            if StatusCode::from_u16(*status).is_err() {
+                issues.at("on_status", |issues| {
+                    issues.at_index(index, |issues| {
+                        issues.error(format!("invalid status: {}", status))
+                    })
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(yaml: &str) -> RetryPolicy {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_max() {
+        let ms = Duration::from_millis;
+        let cases = [
+            ("jitter: false", vec![ms(200), ms(400), ms(800), ms(1600)]),
+            (
+                "{jitter: false, backoff: 1s, max_backoff: 3s}",
+                vec![ms(1000), ms(2000), ms(3000), ms(3000)],
+            ),
+            (
+                "{jitter: false, backoff: 5s}",
+                vec![ms(5000), ms(10_000), ms(10_000), ms(10_000)],
+            ),
+            ("{jitter: false, backoff: 0}", vec![ms(0); 4]),
+        ];
+        for (yaml, expected) in cases {
+            let policy = policy(yaml);
+            let delays: Vec<_> = (1..=4).map(|attempt| policy.delay(attempt)).collect();
+            assert_eq!(delays, expected, "{}", yaml);
+        }
+        // Large attempts saturate instead of overflowing.
+        assert_eq!(policy("jitter: false").delay(u32::MAX), DEFAULT_MAX_BACKOFF);
+    }
+
+    #[test]
+    fn jitter_keeps_delay_between_half_and_full_backoff() {
+        let policy = policy("{backoff: 1s, max_backoff: 4s}");
+        for attempt in 1..=5 {
+            let full = Duration::from_secs(1 << (attempt - 1).min(2));
+            let delay = policy.delay(attempt);
+            assert!(
+                full / 2 <= delay && delay <= full,
+                "attempt {}: {:?}",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn retry_status_uses_the_configured_statuses_only() {
+        let cases = [
+            ("{}", vec![502, 503, 504], vec![200, 404, 500, 429]),
+            (
+                "on_status: [429, 500]",
+                vec![429, 500],
+                vec![200, 502, 503, 504],
+            ),
+            ("on_status: []", vec![], vec![429, 500, 502, 503, 504]),
+        ];
+        for (yaml, retried, not_retried) in cases {
+            let policy = policy(yaml);
+            for status in retried {
+                let status = StatusCode::from_u16(status).unwrap();
+                assert!(policy.retry_status(status), "{}: {}", yaml, status);
+            }
+            for status in not_retried {
+                let status = StatusCode::from_u16(status).unwrap();
+                assert!(!policy.retry_status(status), "{}: {}", yaml, status);
+            }
+        }
+    }
+
+    #[test]
+    fn max_attempts_is_at_least_one() {
+        for (yaml, expected) in [("{}", 1), ("max_attempts: 0", 1), ("max_attempts: 3", 3)] {
+            assert_eq!(policy(yaml).max_attempts(), expected, "{}", yaml);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! JSON Schema of config files, for editors such as yaml-language-server.
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde_json::{Value, json};
+
+const METHODS: [&str; 9] = [
+    "GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS", "TRACE", "CONNECT",
+];
+
+/// Generate the schema of a config file. Besides the fields of `T` it covers
+/// `include`, `defaults` and the `extends` of profiles, which are resolved on load.
+pub fn config_schema<T: JsonSchema>() -> Schema {
+    let mut schema = schemars::schema_for!(T);
+    // A request may inherit its `url` from `defaults` or another profile,
+    // or get it from a target, so it is only checked on load.
+    if let Some(Value::Object(request)) = schema.pointer_mut("/$defs/RequestProfile") {
+        request.remove("required");
+    }
+    let profile_ref = schema
+        .pointer("/additionalProperties/$ref")
+        .and_then(Value::as_str)
+        .map(|r| r.trim_start_matches("#/$defs/").to_string());
+    // Reject unknown keys to catch typos. The profile itself is checked by the
+    // wrapper below, which also allows `extends`. `additionalProperties` would
+    // reject keys of flattened fields and `extends`, so it is replaced.
+    if let Some(Value::Object(defs)) = schema.get_mut("$defs") {
+        for (name, def) in defs.iter_mut() {
+            let Value::Object(def) = def else {
+                continue;
+            };
+            if def.get("additionalProperties") == Some(&false.into()) {
+                def.remove("additionalProperties");
+            }
+            if Some(name) != profile_ref.as_ref() && def.get("type") == Some(&json!("object")) {
+                def.insert("unevaluatedProperties".to_string(), false.into());
+            }
+        }
+    }
+    // URLs may be a path joined to a target, or contain `${VAR}` and `{{var}}`
+    // which are only resolved on load.
+    if let Some(defs) = schema.get_mut("$defs") {
+        strip_uri_format(defs);
+    }
+    if let Some(profile) = schema.get_mut("additionalProperties") {
+        *profile = json!({
+            "allOf": [profile.take()],
+            "properties": {
+                "extends": {
+                    "description": "Name of a profile to inherit settings from.",
+                    "type": "string",
+                },
+            },
+            "unevaluatedProperties": false,
+        });
+    }
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            "include".to_string(),
+            json!({
+                "description": "Glob patterns of config files to load, relative to this file.",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } },
+                ],
+            }),
+        );
+        properties.insert(
+            "defaults".to_string(),
+            json!({
+                "description": "Settings inherited by every profile.",
+                "type": "object",
+            }),
+        );
+    }
+    schema
+}
+
+/// Remove `"format": "uri"` from every schema in `value`.
+fn strip_uri_format(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if object.get("format") == Some(&json!("uri")) {
+                object.remove("format");
+            }
+            object.values_mut().for_each(strip_uri_format);
+        }
+        Value::Array(array) => array.iter_mut().for_each(strip_uri_format),
+        _ => {}
+    }
+}
+
+pub fn method(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "description": "HTTP method, defaults to GET.",
+        "type": "string",
+        "enum": METHODS,
+    })
+}
+
+/// Header names and values, where `null` removes a header inherited with `extends`.
+pub fn header_map(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "object",
+        "propertyNames": { "pattern": "^[!#$%&'*+.^_`|~0-9A-Za-z-]+$" },
+        "additionalProperties": { "type": ["string", "null"] },
+    })
+}
+
+pub fn json_object(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": ["object", "null"],
+    })
+}
+
+pub fn vars(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "object",
+        "additionalProperties": { "type": ["string", "number", "boolean"] },
+    })
+}
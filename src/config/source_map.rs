@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use super::issues::{Location, join_path};
+
+/// Location of every key and value of config files, by path like `todo.req1.headers`.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    locations: HashMap<String, Location>,
+}
+
+impl SourceMap {
+    /// Record the locations of a YAML or JSON document read from `file`.
+    /// Where several files set the same path, the first one is kept.
+    pub fn add(&mut self, content: &str, file: &str) {
+        let mut indexer = Indexer {
+            map: self,
+            file,
+            frames: Vec::new(),
+        };
+        // Syntax errors are reported when the document is parsed.
+        let _ = Parser::new_from_str(content).load(&mut indexer, false);
+    }
+
+    /// Add the locations of another file, where no file set the same path before.
+    pub fn extend(&mut self, other: &SourceMap) {
+        for (path, location) in &other.locations {
+            self.locations
+                .entry(path.clone())
+                .or_insert_with(|| location.clone());
+        }
+    }
+
+    /// Location of `path`, or of its closest parent found in a file.
+    pub fn locate(&self, path: &str) -> Option<&Location> {
+        let mut path = path;
+        loop {
+            if let Some(location) = self.locations.get(path) {
+                return Some(location);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+/// Walks the events of a YAML document, tracking the path of each value.
+struct Indexer<'a> {
+    map: &'a mut SourceMap,
+    file: &'a str,
+    frames: Vec<Frame>,
+}
+
+/// A mapping or sequence being walked.
+struct Frame {
+    /// Path segment of the container in its parent.
+    segment: Option<String>,
+    kind: FrameKind,
+}
+
+enum FrameKind {
+    /// The key of the current entry, `None` while waiting for the key.
+    Mapping(Option<String>),
+    /// Index of the current item.
+    Sequence(usize),
+}
+
+impl Indexer<'_> {
+    /// Path segment of the next value in the current container.
+    fn segment(&self) -> Option<String> {
+        match &self.frames.last()?.kind {
+            FrameKind::Mapping(key) => Some(key.clone().unwrap_or_default()),
+            FrameKind::Sequence(index) => Some(format!("[{}]", index)),
+        }
+    }
+
+    /// Record the location of the next value, unless its key was recorded already.
+    fn record(&mut self, mark: Marker) {
+        let mut segments = self.container();
+        segments.extend(self.segment());
+        self.insert(&segments, mark);
+    }
+
+    /// Record the location of the current container, unless it was recorded already.
+    fn record_container(&mut self, mark: Marker) {
+        let segments = self.container();
+        self.insert(&segments, mark);
+    }
+
+    /// Path of the current container.
+    fn container(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .filter_map(|f| f.segment.clone())
+            .collect()
+    }
+
+    fn insert(&mut self, segments: &[String], mark: Marker) {
+        self.map
+            .locations
+            .entry(join_path(segments))
+            .or_insert_with(|| Location {
+                file: self.file.to_string(),
+                line: mark.line(),
+                column: mark.col() + 1,
+            });
+    }
+
+    /// Move on to the next entry of the current container.
+    fn next(&mut self) {
+        match self.frames.last_mut().map(|f| &mut f.kind) {
+            Some(FrameKind::Mapping(key)) => *key = None,
+            Some(FrameKind::Sequence(index)) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for Indexer<'_> {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => match self.frames.last_mut().map(|f| &mut f.kind) {
+                Some(FrameKind::Mapping(key @ None)) => {
+                    *key = Some(value);
+                    self.record(mark);
+                    // The start of a block mapping isn't marked where it's written,
+                    // so a mapping is located at its first key.
+                    self.record_container(mark);
+                }
+                _ => {
+                    self.record(mark);
+                    self.next();
+                }
+            },
+            Event::Alias(_) => {
+                self.record(mark);
+                self.next();
+            }
+            Event::MappingStart(..) | Event::SequenceStart(..) => {
+                let kind = match event {
+                    Event::MappingStart(..) => FrameKind::Mapping(None),
+                    _ => {
+                        self.record(mark);
+                        FrameKind::Sequence(0)
+                    }
+                };
+                let segment = self.segment();
+                self.frames.push(Frame { segment, kind });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.frames.pop();
+                self.next();
+            }
+            _ => {}
+        }
+    }
+}
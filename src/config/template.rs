@@ -0,0 +1,196 @@
+use anyhow::{Result, anyhow};
+use regex::{Captures, Regex};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use super::issues::Issues;
+
+/// Matches `{{name}}` with optional spaces around the name.
+/// Anything else in braces is kept as is, e.g. a mustache template sent in a body.
+static TEMPLATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_.-]*)\s*\}\}").unwrap());
+
+const VARS: &str = "vars";
+/// Keys of a diff profile holding a request, which could have its own `vars`.
+const REQUEST_KEYS: [&str; 3] = ["req1", "req2", "request"];
+
+type Vars = HashMap<String, String>;
+
+/// Render `{{name}}` in every string of a config, where top-level keys are profile names.
+/// Variables are looked up in `overrides`, then the request, profile and config `vars`.
+pub fn render_config(config: &mut Value, overrides: &[(String, String)], issues: &mut Issues) {
+    let Value::Mapping(profiles) = config else {
+        return;
+    };
+
+    let vars = scope(&Vars::new(), profiles, overrides, issues);
+    issues.retain_profiles(profiles, |name, profile, issues| {
+        if name != VARS {
+            render_profile(profile, &vars, overrides, issues);
+        }
+    });
+}
+
+fn render_profile(
+    profile: &mut Value,
+    vars: &Vars,
+    overrides: &[(String, String)],
+    issues: &mut Issues,
+) {
+    let Value::Mapping(profile) = profile else {
+        return render_value(profile, vars, issues);
+    };
+
+    let vars = scope(vars, profile, overrides, issues);
+    for (key, value) in profile.iter_mut() {
+        issues.at_key(key, |issues| match (key.as_str(), value) {
+            (Some(VARS), _) => {}
+            (Some(key), Value::Mapping(req)) if REQUEST_KEYS.contains(&key) => {
+                let vars = scope(&vars, req, overrides, issues);
+                render_mapping(req, &vars, issues);
+            }
+            (_, value) => render_value(value, &vars, issues),
+        });
+    }
+}
+
+/// Extend the outer variables with the `vars` of a mapping, keeping `overrides` on top.
+/// Scalar values are stored as strings, so `vars` deserialize into a string map.
+fn scope(
+    outer: &Vars,
+    mapping: &mut Mapping,
+    overrides: &[(String, String)],
+    issues: &mut Issues,
+) -> Vars {
+    let mut vars = outer.clone();
+    if let Some(Value::Mapping(own)) = mapping.get_mut(VARS) {
+        issues.at(VARS, |issues| {
+            for (name, value) in own.iter_mut() {
+                let Some(name) = name.as_str() else {
+                    issues.error("variable names must be strings");
+                    continue;
+                };
+                let text = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => {
+                        issues.at(name, |issues| issues.error("variable must be a scalar"));
+                        continue;
+                    }
+                };
+                *value = Value::String(text.clone());
+                vars.insert(name.to_string(), text);
+            }
+        });
+    }
+    vars.extend(overrides.iter().cloned());
+    vars
+}
+
+fn render_mapping(mapping: &mut Mapping, vars: &Vars, issues: &mut Issues) {
+    for (key, value) in mapping.iter_mut() {
+        if key.as_str() != Some(VARS) {
+            issues.at_key(key, |issues| render_value(value, vars, issues));
+        }
+    }
+}
+
+fn render_value(value: &mut Value, vars: &Vars, issues: &mut Issues) {
+    match value {
+        Value::String(s) if s.contains("{{") => match render_str(s, vars) {
+            Ok(rendered) => *s = rendered,
+            Err(e) => issues.error(e),
+        },
+        Value::Sequence(seq) => {
+            for (index, value) in seq.iter_mut().enumerate() {
+                issues.at_index(index, |issues| render_value(value, vars, issues));
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, value) in map.iter_mut() {
+                issues.at_key(key, |issues| render_value(value, vars, issues));
+            }
+        }
+        Value::Tagged(tagged) => render_value(&mut tagged.value, vars, issues),
+        _ => {}
+    }
+}
+
+fn render_str(s: &str, vars: &Vars) -> Result<String> {
+    let mut missing = None;
+    let rendered = TEMPLATE.replace_all(s, |caps: &Captures| match vars.get(&caps[1]) {
+        Some(value) => value.clone(),
+        None => {
+            missing.get_or_insert_with(|| caps[1].to_string());
+            String::new()
+        }
+    });
+    match missing {
+        Some(name) => Err(anyhow!("variable `{}` is not defined in `{}`", name, s)),
+        None => Ok(rendered.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a YAML config, returning it and its issues.
+    fn render(yaml: &str, overrides: &[(&str, &str)]) -> (Value, Vec<String>) {
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        let overrides: Vec<_> = overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let mut issues = Issues::default();
+        render_config(&mut config, &overrides, &mut issues);
+        (config, issues.iter().map(|i| i.to_string()).collect())
+    }
+
+    #[test]
+    fn variables_come_from_the_closest_scope() {
+        let (config, issues) = render(
+            r#"
+vars: {host: a.test, env: prod, version: 1}
+p:
+  vars: {env: staging}
+  req1:
+    vars: {host: b.test}
+    url: "http://{{ host }}/{{env}}/v{{version}}"
+  req2: {url: "http://{{host}}/{{env}}/{{user}}", body: "{{ not a var }}"}
+"#,
+            &[("user", "me"), ("version", "2")],
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(config["p"]["req1"]["url"], "http://b.test/staging/v2");
+        assert_eq!(config["p"]["req2"]["url"], "http://a.test/staging/me");
+        assert_eq!(config["p"]["req2"]["body"], "{{ not a var }}");
+    }
+
+    #[test]
+    fn unknown_variables_are_reported_where_they_are_used() {
+        let (_, issues) = render(
+            r#"
+vars: {host: a.test}
+p:
+  req1: {url: "http://{{host}}/{{missing}}", headers: {x-id: "{{id}}"}}
+  req2: {vars: {id: 1}, url: "http://{{host}}/{{id}}"}
+q:
+  vars: {list: [a]}
+  args: ["{{host}}", "{{other}}"]
+"#,
+            &[],
+        );
+        assert_eq!(
+            issues,
+            [
+                "error: p.req1.url: variable `missing` is not defined in `http://{{host}}/{{missing}}`",
+                "error: p.req1.headers.x-id: variable `id` is not defined in `{{id}}`",
+                "error: q.vars.list: variable must be a scalar",
+                "error: q.args[1]: variable `other` is not defined in `{{other}}`",
+            ]
+        );
+    }
+}
@@ -1,33 +1,54 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use std::collections::HashMap;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{LoadConfig, RequestProfile, Validateable};
+use super::{ClientConfig, Issues, LoadConfig, RequestProfile, Validateable, is_default, schema};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct RequestConfig {
+    /// HTTP client settings shared by all profiles.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    /// Template variables shared by all profiles.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    #[schemars(schema_with = "schema::vars")]
+    pub vars: HashMap<String, String>,
     #[serde(flatten)]
     pub profiles: HashMap<String, RequestProfile>,
 }
 
-impl LoadConfig for RequestConfig {}
+impl LoadConfig for RequestConfig {
+    const FILE_NAME: &'static str = "xreq.yaml";
+    const ENV_VAR: &'static str = "XREQ_CONFIG";
+    type Profile = RequestProfile;
 
-impl Validateable for RequestConfig {
-    fn validate(&self) -> Result<()> {
-        for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("failed to validate profile: {}", name))?;
+    fn resolve(&mut self) -> Result<()> {
+        for profile in self.profiles.values_mut() {
+            profile.client = self.client.merge(&profile.client);
         }
-
         Ok(())
     }
 }
 
+impl Validateable for RequestConfig {
+    fn check(&self, issues: &mut Issues) {
+        let mut names: Vec<&String> = self.profiles.keys().collect();
+        names.sort();
+        for name in names {
+            issues.at(name, |issues| self.profiles[name].check(issues));
+        }
+    }
+}
+
 impl RequestConfig {
     pub fn new(profiles: HashMap<String, RequestProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            vars: HashMap::new(),
+            profiles,
+        }
     }
 
     pub fn get_profile(&self, name: &str) -> Option<&RequestProfile> {
@@ -0,0 +1,422 @@
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+use crate::{JsonChange, diff_text, diff_text_side_by_side, mask_secrets, render_changes};
+
+/// Structured result of diffing two responses.
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffResult {
+    /// Whether both responses are identical after filtering.
+    pub equal: bool,
+    pub stats: DiffStats,
+    pub status: SectionDiff,
+    pub headers: SectionDiff,
+    pub body: SectionDiff,
+    /// Structural body changes, only present in JSON diff mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Vec<JsonChange>>,
+    /// Failed attempts of both requests which were retried.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retries: Vec<String>,
+}
+
+/// Exit code when both responses are identical.
+pub const EXIT_IDENTICAL: u8 = 0;
+/// Exit code when the responses differ.
+pub const EXIT_DIFFERENT: u8 = 1;
+/// Exit code for invalid arguments or config.
+pub const EXIT_ERROR: u8 = 2;
+/// Exit code when a request could not be sent or its response could not be read.
+pub const EXIT_REQUEST_FAILED: u8 = 3;
+
+/// Outcome of diffing one profile in a batch run.
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileRun {
+    pub name: String,
+    pub status: RunStatus,
+    #[serde(rename = "elapsed_ms", with = "duration_ms")]
+    pub elapsed: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<DiffResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+}
+
+/// What failed in a profile run with an error.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// A request of the profile is invalid, e.g. its body file can't be read.
+    Config,
+    /// A request could not be sent or its response could not be read.
+    Request,
+    /// The responses could not be diffed, e.g. a body is not valid JSON.
+    Diff,
+}
+
+/// Context of errors in the config of a request, as opposed to failures sending it.
+#[derive(Debug)]
+pub struct ConfigError;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    /// Both responses are identical.
+    Pass,
+    /// The responses differ.
+    Diff,
+    /// A request failed or its response could not be diffed.
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub insertions: usize,
+    pub deletions: usize,
+    pub changes: usize,
+}
+
+/// Line diff of one section (status, headers or body) of two responses.
+#[derive(Debug, Serialize, Clone)]
+pub struct SectionDiff {
+    pub old: String,
+    pub new: String,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A group of changed lines with surrounding context.
+#[derive(Debug, Serialize, Clone)]
+pub struct Hunk {
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffLine {
+    pub tag: LineTag,
+    /// Zero-based line index in the old text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_index: Option<usize>,
+    /// Zero-based line index in the new text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_index: Option<usize>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineTag {
+    Equal,
+    Insert,
+    Delete,
+}
+
+impl From<ChangeTag> for LineTag {
+    fn from(tag: ChangeTag) -> Self {
+        match tag {
+            ChangeTag::Equal => LineTag::Equal,
+            ChangeTag::Insert => LineTag::Insert,
+            ChangeTag::Delete => LineTag::Delete,
+        }
+    }
+}
+
+impl SectionDiff {
+    pub fn new(old: String, new: String) -> Self {
+        let hunks = {
+            let diff = TextDiff::from_lines(&old, &new);
+            diff.grouped_ops(3)
+                .iter()
+                .map(|group| Hunk {
+                    lines: group
+                        .iter()
+                        .flat_map(|op| diff.iter_changes(op))
+                        .map(|change| DiffLine {
+                            tag: change.tag().into(),
+                            old_index: change.old_index(),
+                            new_index: change.new_index(),
+                            value: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                        })
+                        .collect(),
+                })
+                .collect()
+        };
+
+        Self { old, new, hunks }
+    }
+
+    pub fn is_equal(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    fn count(&self, tag: LineTag) -> usize {
+        self.hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .filter(|l| l.tag == tag)
+            .count()
+    }
+}
+
+impl ProfileRun {
+    pub fn new(name: String, elapsed: Duration, result: Result<DiffResult>) -> Self {
+        match result {
+            Ok(result) => Self {
+                name,
+                status: if result.equal {
+                    RunStatus::Pass
+                } else {
+                    RunStatus::Diff
+                },
+                elapsed,
+                result: Some(result),
+                error: None,
+                error_kind: None,
+            },
+            Err(e) => Self {
+                name,
+                status: RunStatus::Error,
+                elapsed,
+                result: None,
+                error: Some(format!("{:#}", e)),
+                error_kind: Some(ErrorKind::of(&e)),
+            },
+        }
+    }
+}
+
+impl ProfileRun {
+    /// Exit code of a run of these profiles, from the worst outcome of any of them.
+    pub fn exit_code(runs: &[ProfileRun]) -> u8 {
+        // Errors other than failed requests, e.g. an invalid response body, take precedence.
+        let request_failed = |r: &ProfileRun| {
+            r.status != RunStatus::Error || r.error_kind == Some(ErrorKind::Request)
+        };
+        match runs.iter().map(|r| r.status).max() {
+            Some(RunStatus::Error) if runs.iter().all(request_failed) => EXIT_REQUEST_FAILED,
+            Some(RunStatus::Error) => EXIT_ERROR,
+            Some(RunStatus::Diff) => EXIT_DIFFERENT,
+            _ => EXIT_IDENTICAL,
+        }
+    }
+
+    /// Replace secrets resolved from the config in every text of the run.
+    pub fn mask_secrets(&mut self) {
+        if let Some(result) = self.result.as_mut() {
+            for section in [&mut result.status, &mut result.headers, &mut result.body] {
+                *section = SectionDiff::new(mask_secrets(&section.old), mask_secrets(&section.new));
+            }
+            for change in result.changes.iter_mut().flatten() {
+                change.path = mask_secrets(&change.path);
+                change
+                    .old
+                    .iter_mut()
+                    .chain(change.new.iter_mut())
+                    .for_each(mask_json);
+            }
+            for retry in result.retries.iter_mut() {
+                *retry = mask_secrets(retry);
+            }
+        }
+        if let Some(error) = self.error.as_mut() {
+            *error = mask_secrets(error);
+        }
+    }
+}
+
+fn mask_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => *s = mask_secrets(s),
+        serde_json::Value::Array(arr) => arr.iter_mut().for_each(mask_json),
+        serde_json::Value::Object(obj) => obj.values_mut().for_each(mask_json),
+        _ => {}
+    }
+}
+
+impl ErrorKind {
+    fn of(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<ConfigError>().is_some() {
+            ErrorKind::Config
+        } else if e.chain().any(|c| c.is::<reqwest::Error>()) {
+            ErrorKind::Request
+        } else {
+            ErrorKind::Diff
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Config => write!(f, "config"),
+            ErrorKind::Request => write!(f, "request"),
+            ErrorKind::Diff => write!(f, "diff"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid request")
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunStatus::Pass => write!(f, "PASS"),
+            RunStatus::Diff => write!(f, "DIFF"),
+            RunStatus::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+mod duration_ms {
+    use serde::Serializer;
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_millis() as u64)
+    }
+}
+
+impl DiffResult {
+    pub fn new(
+        status: SectionDiff,
+        headers: SectionDiff,
+        body: SectionDiff,
+        changes: Option<Vec<JsonChange>>,
+    ) -> Self {
+        let sections = [&status, &headers, &body];
+        let stats = DiffStats {
+            insertions: sections.iter().map(|s| s.count(LineTag::Insert)).sum(),
+            deletions: sections.iter().map(|s| s.count(LineTag::Delete)).sum(),
+            changes: changes.as_ref().map_or(0, Vec::len),
+        };
+        let body_equal = match &changes {
+            Some(changes) => changes.is_empty(),
+            None => body.is_equal(),
+        };
+        let equal = status.is_equal() && headers.is_equal() && body_equal;
+
+        Self {
+            equal,
+            stats,
+            status,
+            headers,
+            body,
+            changes,
+            retries: vec![],
+        }
+    }
+
+    /// Render the result as a colored unified diff for the terminal.
+    pub fn render_terminal(&self) -> Result<String> {
+        let mut output = String::new();
+        for section in [&self.status, &self.headers] {
+            write!(&mut output, "{}", diff_text(&section.old, &section.new)?)?;
+        }
+        match &self.changes {
+            Some(changes) => write!(&mut output, "{}", render_changes(changes)?)?,
+            None => write!(
+                &mut output,
+                "{}",
+                diff_text(&self.body.old, &self.body.new)?
+            )?,
+        }
+
+        Ok(output)
+    }
+
+    /// Render the result as a colored two-column diff fitting in `width` terminal columns.
+    pub fn render_side_by_side(&self, width: usize) -> Result<String> {
+        let mut output = String::new();
+        for section in [&self.status, &self.headers] {
+            write!(
+                &mut output,
+                "{}",
+                diff_text_side_by_side(&section.old, &section.new, width)?
+            )?;
+        }
+        match &self.changes {
+            Some(changes) => write!(&mut output, "{}", render_changes(changes)?)?,
+            None => write!(
+                &mut output,
+                "{}",
+                diff_text_side_by_side(&self.body.old, &self.body.new, width)?
+            )?,
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    fn pass() -> ProfileRun {
+        let section = SectionDiff::new("a\n".into(), "a\n".into());
+        let result = DiffResult::new(section.clone(), section.clone(), section, None);
+        ProfileRun::new("pass".into(), Duration::ZERO, Ok(result))
+    }
+
+    fn diff() -> ProfileRun {
+        let section = SectionDiff::new("a\n".into(), "b\n".into());
+        let result = DiffResult::new(section.clone(), section.clone(), section, None);
+        ProfileRun::new("diff".into(), Duration::ZERO, Ok(result))
+    }
+
+    fn error(request_failed: bool) -> ProfileRun {
+        let mut run = ProfileRun::new("error".into(), Duration::ZERO, Err(anyhow!("failed")));
+        if request_failed {
+            run.error_kind = Some(ErrorKind::Request);
+        }
+        run
+    }
+
+    #[test]
+    fn exit_code_is_from_the_worst_run() {
+        let cases = [
+            (vec![], EXIT_IDENTICAL),
+            (vec![pass()], EXIT_IDENTICAL),
+            (vec![pass(), diff()], EXIT_DIFFERENT),
+            (vec![error(false)], EXIT_ERROR),
+            (vec![diff(), error(false)], EXIT_ERROR),
+            (vec![error(true)], EXIT_REQUEST_FAILED),
+            (vec![pass(), diff(), error(true)], EXIT_REQUEST_FAILED),
+            (vec![error(true), error(true)], EXIT_REQUEST_FAILED),
+            (vec![error(true), error(false)], EXIT_ERROR),
+        ];
+        for (runs, expected) in cases {
+            let statuses: Vec<_> = runs.iter().map(|r| (r.status, r.error_kind)).collect();
+            assert_eq!(ProfileRun::exit_code(&runs), expected, "{:?}", statuses);
+        }
+    }
+
+    #[test]
+    fn new_run_status_follows_the_result() {
+        assert_eq!(pass().status, RunStatus::Pass);
+        assert_eq!(diff().status, RunStatus::Diff);
+        let run = error(false);
+        assert_eq!(run.status, RunStatus::Error);
+        assert_eq!(run.error.as_deref(), Some("failed"));
+    }
+
+    #[test]
+    fn error_kind_is_from_the_error_chain() {
+        let config = Err::<(), _>(anyhow!("no such file"))
+            .context(ConfigError)
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&config), ErrorKind::Config);
+        assert_eq!(config.to_string(), "invalid request");
+        let diff = anyhow!("expected value at line 1 column 1").context("invalid JSON body");
+        assert_eq!(ErrorKind::of(&diff), ErrorKind::Diff);
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use similar::{DiffOp, TextDiff};
+
+use crate::{ChangeKind, ProfileRun, RunStatus, SectionDiff, highlight_html_lines};
+
+/// Number of unchanged lines kept visible around changes.
+const CONTEXT: usize = 3;
+const THEME: &str = "InspiredGitHub";
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #24292f; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+table.index td, table.index th { padding: .2em 1em; text-align: left; }
+table.diff { width: 100%; border-collapse: collapse; table-layout: fixed; font: 12px/1.5 monospace; }
+table.diff td { padding: 0 .5em; vertical-align: top; white-space: pre-wrap; word-break: break-all; }
+table.diff td.ln { width: 3em; color: #8c959f; text-align: right; user-select: none; }
+table.diff td.fold { padding: 0; }
+table.diff td.fold summary { background: #ddf4ff; color: #57606a; padding: 0 .5em; cursor: pointer; }
+tr.del td.old { background: #ffebe9; }
+tr.ins td.new { background: #e6ffec; }
+td.empty { background: #f6f8fa; }
+.status { font-size: .7em; padding: .1em .5em; border-radius: 1em; color: #fff; vertical-align: middle; }
+.status.pass { background: #1a7f37; }
+.status.diff { background: #9a6700; }
+.status.error { background: #cf222e; }
+pre.error { background: #ffebe9; padding: 1em; white-space: pre-wrap; }
+ul.changes { font-family: monospace; }
+li.added { color: #1a7f37; }
+li.removed { color: #cf222e; }
+li.changed { color: #9a6700; }
+"#;
+
+/// Render profile runs as a self-contained HTML page with side-by-side
+/// diffs of status, headers and body. An index is added for multiple profiles.
+pub fn html_report(runs: &[ProfileRun]) -> Result<String> {
+    let mut output = String::new();
+    writeln!(&mut output, "<!DOCTYPE html>")?;
+    writeln!(&mut output, r#"<html><head><meta charset="utf-8">"#)?;
+    writeln!(&mut output, "<title>rdiff report</title>")?;
+    writeln!(&mut output, "<style>{}</style></head><body>", STYLE)?;
+    writeln!(&mut output, "<h1>rdiff report</h1>")?;
+
+    if runs.len() > 1 {
+        writeln!(&mut output, r#"<table class="index">"#)?;
+        writeln!(
+            &mut output,
+            "<tr><th>Profile</th><th>Status</th><th>Time</th></tr>"
+        )?;
+        for (idx, run) in runs.iter().enumerate() {
+            writeln!(
+                &mut output,
+                r##"<tr><td><a href="#profile-{}">{}</a></td><td>{}</td><td>{}ms</td></tr>"##,
+                idx,
+                escape_html(&run.name),
+                status_badge(run.status),
+                run.elapsed.as_millis()
+            )?;
+        }
+        writeln!(&mut output, "</table>")?;
+    }
+
+    for (idx, run) in runs.iter().enumerate() {
+        writeln!(
+            &mut output,
+            r#"<section id="profile-{}"><h2>{} {}</h2>"#,
+            idx,
+            escape_html(&run.name),
+            status_badge(run.status)
+        )?;
+        if let Some(error) = &run.error {
+            writeln!(
+                &mut output,
+                r#"<pre class="error">{}</pre>"#,
+                escape_html(error)
+            )?;
+        }
+        if let Some(result) = &run.result {
+            write_section(&mut output, "Status", &result.status, "yaml")?;
+            write_section(&mut output, "Headers", &result.headers, "yaml")?;
+            if let Some(changes) = &result.changes {
+                writeln!(&mut output, "<h3>Body changes</h3>")?;
+                writeln!(&mut output, r#"<ul class="changes">"#)?;
+                for change in changes {
+                    let class = match change.kind {
+                        ChangeKind::Added => "added",
+                        ChangeKind::Removed => "removed",
+                        ChangeKind::Changed => "changed",
+                    };
+                    writeln!(
+                        &mut output,
+                        r#"<li class="{}">{}</li>"#,
+                        class,
+                        escape_html(&change.to_string())
+                    )?;
+                }
+                writeln!(&mut output, "</ul>")?;
+            }
+            let syntax = match serde_json::from_str::<serde_json::Value>(&result.body.old) {
+                Ok(_) => "json",
+                Err(_) => "txt",
+            };
+            write_section(&mut output, "Body", &result.body, syntax)?;
+        }
+        writeln!(&mut output, "</section>")?;
+    }
+
+    writeln!(&mut output, "</body></html>")?;
+    Ok(output)
+}
+
+/// Write a two-column diff table of a section, folding long unchanged regions.
+fn write_section(
+    output: &mut String,
+    title: &str,
+    section: &SectionDiff,
+    syntax: &str,
+) -> Result<()> {
+    let diff = TextDiff::from_lines(&section.old, &section.new);
+    let old = highlight_html_lines(diff.old_slices(), syntax, Some(THEME))?;
+    let new = highlight_html_lines(diff.new_slices(), syntax, Some(THEME))?;
+    let ops = diff.ops();
+
+    writeln!(output, "<h3>{}</h3>", title)?;
+    writeln!(output, r#"<table class="diff">"#)?;
+    for (idx, op) in ops.iter().enumerate() {
+        match *op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                // Keep context after the previous change and before the next one.
+                let head = if idx > 0 { CONTEXT.min(len) } else { 0 };
+                let tail = if idx + 1 < ops.len() {
+                    CONTEXT.min(len - head)
+                } else {
+                    0
+                };
+                let row = |output: &mut String, i: usize| {
+                    write_row(
+                        output,
+                        "eq",
+                        Some((old_index + i, line(&old, old_index + i))),
+                        Some((new_index + i, line(&new, new_index + i))),
+                    )
+                };
+                for i in 0..head {
+                    row(output, i)?;
+                }
+                if len - head - tail > 0 {
+                    writeln!(
+                        output,
+                        r#"<tr><td class="fold" colspan="4"><details><summary>{} unchanged lines</summary><table class="diff">"#,
+                        len - head - tail
+                    )?;
+                    for i in head..len - tail {
+                        row(output, i)?;
+                    }
+                    writeln!(output, "</table></details></td></tr>")?;
+                }
+                for i in len - tail..len {
+                    row(output, i)?;
+                }
+            }
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => {
+                for i in old_index..old_index + old_len {
+                    write_row(output, "del", Some((i, line(&old, i))), None)?;
+                }
+            }
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => {
+                for i in new_index..new_index + new_len {
+                    write_row(output, "ins", None, Some((i, line(&new, i))))?;
+                }
+            }
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                for i in 0..old_len.max(new_len) {
+                    let left = (i < old_len).then(|| (old_index + i, line(&old, old_index + i)));
+                    let right = (i < new_len).then(|| (new_index + i, line(&new, new_index + i)));
+                    write_row(output, "del ins", left, right)?;
+                }
+            }
+        }
+    }
+    writeln!(output, "</table>")?;
+
+    Ok(())
+}
+
+/// Get a highlighted line, or an empty one if the diff and highlighting disagree on lines.
+fn line(lines: &[String], i: usize) -> &str {
+    lines.get(i).map_or("", String::as_str)
+}
+
+fn write_row(
+    output: &mut String,
+    class: &str,
+    old: Option<(usize, &str)>,
+    new: Option<(usize, &str)>,
+) -> Result<()> {
+    write!(output, r#"<tr class="{}">"#, class)?;
+    for (side, cell) in [("old", old), ("new", new)] {
+        match cell {
+            Some((idx, html)) => write!(
+                output,
+                r#"<td class="ln">{}</td><td class="{}">{}</td>"#,
+                idx + 1,
+                side,
+                html
+            )?,
+            None => write!(
+                output,
+                r#"<td class="ln empty"></td><td class="empty"></td>"#
+            )?,
+        }
+    }
+    writeln!(output, "</tr>")?;
+
+    Ok(())
+}
+
+fn status_badge(status: RunStatus) -> String {
+    let class = match status {
+        RunStatus::Pass => "pass",
+        RunStatus::Diff => "diff",
+        RunStatus::Error => "error",
+    };
+    format!(r#"<span class="status {}">{}</span>"#, class, status)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffResult;
+    use std::time::Duration;
+
+    #[test]
+    fn lone_carriage_returns_are_lines() {
+        let section = |old: &str, new: &str| SectionDiff::new(old.to_string(), new.to_string());
+        let result = DiffResult::new(
+            section("200\n", "200\n"),
+            section("content-type: text/plain\n", "content-type: text/plain\n"),
+            section("line1\rline2\rline3\rx", "line1\rline2 changed\rline3\r"),
+            None,
+        );
+        let run = ProfileRun::new("cr".to_string(), Duration::ZERO, Ok(result));
+
+        let html = html_report(&[run]).unwrap();
+        for (class, line) in [
+            ("eq", "line1"),
+            ("del ins", "line2 changed"),
+            ("eq", "line3"),
+            ("del", "x"),
+        ] {
+            let row = html.lines().find(|row| {
+                row.starts_with(&format!(r#"<tr class="{}"#, class)) && row.contains(line)
+            });
+            assert!(row.is_some(), "no {} row with {}: {}", class, line, html);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::{self, Write};
+
+use anyhow::Result;
+use console::Style;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The kind of a structural change between two JSON values.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A single difference found at `path` when walking two JSON trees.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for JsonChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (None, Some(v)) | (Some(v), None) => write!(f, "{}: {}", self.path, v),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Walk two JSON values and collect added, removed and changed values by JSON path.
+pub fn diff_json(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = Vec::new();
+    walk("$".to_string(), old, new, &mut changes);
+    changes
+}
+
+fn walk(path: String, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(o1), Value::Object(o2)) => {
+            let mut keys: Vec<&String> = o1.keys().chain(o2.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                let child = push_key(&path, k);
+                match (o1.get(k), o2.get(k)) {
+                    (Some(v1), Some(v2)) => walk(child, v1, v2, changes),
+                    (Some(v1), None) => changes.push(removed(child, v1)),
+                    (None, Some(v2)) => changes.push(added(child, v2)),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (Value::Array(a1), Value::Array(a2)) => {
+            for i in 0..a1.len().max(a2.len()) {
+                let child = format!("{}[{}]", path, i);
+                match (a1.get(i), a2.get(i)) {
+                    (Some(v1), Some(v2)) => walk(child, v1, v2, changes),
+                    (Some(v1), None) => changes.push(removed(child, v1)),
+                    (None, Some(v2)) => changes.push(added(child, v2)),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (v1, v2) if v1 != v2 => changes.push(JsonChange {
+            path,
+            kind: ChangeKind::Changed,
+            old: Some(v1.clone()),
+            new: Some(v2.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn push_key(path: &str, key: &str) -> String {
+    let is_ident = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_ident {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::from(key))
+    }
+}
+
+fn added(path: String, v: &Value) -> JsonChange {
+    JsonChange {
+        path,
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(v.clone()),
+    }
+}
+
+fn removed(path: String, v: &Value) -> JsonChange {
+    JsonChange {
+        path,
+        kind: ChangeKind::Removed,
+        old: Some(v.clone()),
+        new: None,
+    }
+}
+
+/// Render structural changes as colored terminal output, one change per line.
+pub fn render_changes(changes: &[JsonChange]) -> Result<String> {
+    let mut output = String::new();
+    for change in changes {
+        let (sign, s) = match change.kind {
+            ChangeKind::Removed => ("-", Style::new().red()),
+            ChangeKind::Added => ("+", Style::new().green()),
+            ChangeKind::Changed => ("~", Style::new().yellow()),
+        };
+        writeln!(
+            &mut output,
+            "{} {}",
+            s.apply_to(sign).bold(),
+            s.apply_to(change)
+        )?;
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn change(path: &str, kind: ChangeKind, old: Option<Value>, new: Option<Value>) -> JsonChange {
+        JsonChange {
+            path: path.to_string(),
+            kind,
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn diff_json_collects_changes_by_path() {
+        use ChangeKind::*;
+        let cases = [
+            (json!({"a": 1}), json!({"a": 1}), vec![]),
+            (
+                json!({"a": 1}),
+                json!({"a": 2}),
+                vec![change("$.a", Changed, Some(json!(1)), Some(json!(2)))],
+            ),
+            (
+                json!({"b": 1, "a": {"x": true}}),
+                json!({"c": [1]}),
+                vec![
+                    change("$.a", Removed, Some(json!({"x": true})), None),
+                    change("$.b", Removed, Some(json!(1)), None),
+                    change("$.c", Added, None, Some(json!([1]))),
+                ],
+            ),
+            (
+                json!({"items": [{"id": 1}, {"id": 2}]}),
+                json!({"items": [{"id": 1}, {"id": 3}, {"id": 4}]}),
+                vec![
+                    change("$.items[1].id", Changed, Some(json!(2)), Some(json!(3))),
+                    change("$.items[2]", Added, None, Some(json!({"id": 4}))),
+                ],
+            ),
+            (
+                json!([1, 2]),
+                json!([1]),
+                vec![change("$[1]", Removed, Some(json!(2)), None)],
+            ),
+            (
+                json!({"a key": 1, "1st": 1, "_ok": 1}),
+                json!({"a key": 2, "1st": 2, "_ok": 2}),
+                vec![
+                    change(r#"$["1st"]"#, Changed, Some(json!(1)), Some(json!(2))),
+                    change("$._ok", Changed, Some(json!(1)), Some(json!(2))),
+                    change(r#"$["a key"]"#, Changed, Some(json!(1)), Some(json!(2))),
+                ],
+            ),
+            (
+                json!({"a": {"b": 1}}),
+                json!({"a": [1]}),
+                vec![change(
+                    "$.a",
+                    Changed,
+                    Some(json!({"b": 1})),
+                    Some(json!([1])),
+                )],
+            ),
+            (
+                json!(1),
+                json!(1.0),
+                vec![change("$", Changed, Some(json!(1)), Some(json!(1.0)))],
+            ),
+        ];
+        for (old, new, expected) in cases {
+            assert_eq!(diff_json(&old, &new), expected, "{} -> {}", old, new);
+        }
+    }
+
+    #[test]
+    fn changes_display_their_values() {
+        use ChangeKind::*;
+        let cases = [
+            (
+                change("$.a", Changed, Some(json!(1)), Some(json!("x"))),
+                r#"$.a: 1 -> "x""#,
+            ),
+            (change("$.a", Added, None, Some(json!([1]))), "$.a: [1]"),
+            (change("$.a", Removed, Some(json!(null)), None), "$.a: null"),
+        ];
+        for (change, expected) in cases {
+            assert_eq!(change.to_string(), expected);
+        }
+    }
+}
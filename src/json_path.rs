@@ -0,0 +1,454 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+/// A JSONPath-like expression used to select nodes in a JSON tree.
+///
+/// Supported syntax: `data.meta.id`, `$.items[0]`, `items[*].updated_at`,
+/// `$["a key"]` and recursive descent `$..trace_id`. A leading `$` is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+    /// Apply the following segment at any depth.
+    Descend,
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = |reason: &str| anyhow!("invalid path `{}`: {}", s, reason);
+        let mut rest = s.trim();
+        let mut segments = Vec::new();
+
+        match rest.strip_prefix('$') {
+            Some(r) => rest = r,
+            // A bare leading key behaves as if it was prefixed with `$.`.
+            None if !rest.starts_with(['.', '[']) => {
+                let (key, r) = take_key(rest);
+                segments.push(parse_key(key).ok_or_else(|| err("expected key"))?);
+                rest = r;
+            }
+            None => {}
+        }
+
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix("..") {
+                segments.push(Segment::Descend);
+                if r.starts_with('[') {
+                    rest = r;
+                    continue;
+                }
+                let (key, r) = take_key(r);
+                segments.push(parse_key(key).ok_or_else(|| err("expected key after `..`"))?);
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix('.') {
+                let (key, r) = take_key(r);
+                segments.push(parse_key(key).ok_or_else(|| err("expected key after `.`"))?);
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix('[') {
+                let end = r.find(']').ok_or_else(|| err("missing `]`"))?;
+                let inner = r[..end].trim();
+                let segment = if inner == "*" {
+                    Segment::Wildcard
+                } else if let Ok(idx) = inner.parse::<usize>() {
+                    Segment::Index(idx)
+                } else if let Ok(key) = serde_json::from_str::<String>(inner) {
+                    Segment::Key(key)
+                } else if let Some(key) =
+                    inner.strip_prefix('\'').and_then(|k| k.strip_suffix('\''))
+                {
+                    Segment::Key(key.to_string())
+                } else {
+                    return Err(err("expected index, `*` or quoted key inside `[]`"));
+                };
+                segments.push(segment);
+                rest = &r[end + 1..];
+            } else {
+                return Err(err("expected `.` or `[`"));
+            }
+        }
+
+        if segments.is_empty() {
+            return Err(err("path selects the whole document"));
+        }
+        if segments.last() == Some(&Segment::Descend) {
+            return Err(err("path must not end with `..`"));
+        }
+
+        Ok(Self {
+            raw: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl JsonPath {
+    /// Remove every node matched by this path from `json`.
+    pub fn remove(&self, json: &mut Value) {
+        remove_at(json, &self.segments);
+    }
+
+    /// Set the node at this path of keys and indexes to `value`, creating missing
+    /// objects and arrays on the way. An index may append to an array, but not skip items.
+    pub fn set(&self, json: &mut Value, value: Value) -> Result<()> {
+        let mut node = json;
+        for (i, segment) in self.segments.iter().enumerate() {
+            let parent = || match i {
+                0 => "the document".to_string(),
+                _ => format!("`{}`", format_segments(&self.segments[..i])),
+            };
+            let err = |expected: &str| {
+                anyhow!(
+                    "cannot set `{}`, {} is not {}",
+                    self.raw,
+                    parent(),
+                    expected
+                )
+            };
+            node = match segment {
+                Segment::Key(key) => {
+                    if node.is_null() {
+                        *node = Value::Object(Default::default());
+                    }
+                    node.as_object_mut()
+                        .ok_or_else(|| err("an object"))?
+                        .entry(key.clone())
+                        .or_insert(Value::Null)
+                }
+                Segment::Index(index) => {
+                    if node.is_null() {
+                        *node = Value::Array(Vec::new());
+                    }
+                    let items = node.as_array_mut().ok_or_else(|| err("an array"))?;
+                    if *index > items.len() {
+                        return Err(anyhow!(
+                            "cannot set `{}`, {} can only be appended to at index {}",
+                            self.raw,
+                            parent(),
+                            items.len()
+                        ));
+                    }
+                    if *index == items.len() {
+                        items.push(Value::Null);
+                    }
+                    &mut items[*index]
+                }
+                Segment::Wildcard | Segment::Descend => {
+                    return Err(anyhow!(
+                        "cannot set `{}`, only keys and indexes can be set",
+                        self.raw
+                    ));
+                }
+            };
+        }
+        *node = value;
+        Ok(())
+    }
+
+    /// Apply `f` to every node matched by this path in `json`.
+    pub fn for_each_mut(&self, json: &mut Value, f: &mut impl FnMut(&mut Value)) {
+        visit_at(json, &self.segments, f);
+    }
+}
+
+fn take_key(s: &str) -> (&str, &str) {
+    let end = s.find(['.', '[']).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+fn parse_key(key: &str) -> Option<Segment> {
+    match key.trim() {
+        "" => None,
+        k if k.contains(']') => None,
+        "*" => Some(Segment::Wildcard),
+        k => Some(Segment::Key(k.to_string())),
+    }
+}
+
+/// Format segments of a path like `items[0].qty`, to name the parent of a node.
+fn format_segments(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) if path.is_empty() => path.push_str(key),
+            Segment::Key(key) => path.push_str(&format!(".{}", key)),
+            Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            Segment::Wildcard => path.push_str("[*]"),
+            Segment::Descend => path.push('.'),
+        }
+    }
+    path
+}
+
+fn remove_at(json: &mut Value, segments: &[Segment]) {
+    let Some((first, rest)) = segments.split_first() else {
+        return;
+    };
+
+    match (first, json) {
+        (Segment::Descend, json) => {
+            remove_at(json, rest);
+            for child in children_mut(json) {
+                remove_at(child, segments);
+            }
+        }
+        (Segment::Key(k), Value::Object(obj)) => {
+            if rest.is_empty() {
+                obj.remove(k);
+            } else if let Some(child) = obj.get_mut(k) {
+                remove_at(child, rest);
+            }
+        }
+        (Segment::Index(i), Value::Array(arr)) => {
+            if rest.is_empty() {
+                if *i < arr.len() {
+                    arr.remove(*i);
+                }
+            } else if let Some(child) = arr.get_mut(*i) {
+                remove_at(child, rest);
+            }
+        }
+        (Segment::Wildcard, Value::Object(obj)) if rest.is_empty() => obj.clear(),
+        (Segment::Wildcard, Value::Array(arr)) if rest.is_empty() => arr.clear(),
+        (Segment::Wildcard, json) => {
+            for child in children_mut(json) {
+                remove_at(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn visit_at(json: &mut Value, segments: &[Segment], f: &mut impl FnMut(&mut Value)) {
+    let Some((first, rest)) = segments.split_first() else {
+        f(json);
+        return;
+    };
+
+    match (first, json) {
+        (Segment::Descend, json) => {
+            visit_at(json, rest, f);
+            for child in children_mut(json) {
+                visit_at(child, segments, f);
+            }
+        }
+        (Segment::Key(k), Value::Object(obj)) => {
+            if let Some(child) = obj.get_mut(k) {
+                visit_at(child, rest, f);
+            }
+        }
+        (Segment::Index(i), Value::Array(arr)) => {
+            if let Some(child) = arr.get_mut(*i) {
+                visit_at(child, rest, f);
+            }
+        }
+        (Segment::Wildcard, json) => {
+            for child in children_mut(json) {
+                visit_at(child, rest, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn children_mut(json: &mut Value) -> Box<dyn Iterator<Item = &mut Value> + '_> {
+    match json {
+        Value::Object(obj) => Box::new(obj.values_mut()),
+        Value::Array(arr) => Box::new(arr.iter_mut()),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn from_str_parses_segments() {
+        use Segment::*;
+        let key = |k: &str| Key(k.to_string());
+        let cases = [
+            ("id", vec![key("id")]),
+            ("$.data.meta.id", vec![key("data"), key("meta"), key("id")]),
+            ("items[0].qty", vec![key("items"), Index(0), key("qty")]),
+            ("items[*].at", vec![key("items"), Wildcard, key("at")]),
+            ("items.*", vec![key("items"), Wildcard]),
+            ("$..trace_id", vec![Descend, key("trace_id")]),
+            ("a..[0]", vec![key("a"), Descend, Index(0)]),
+            (r#"$["a key"]"#, vec![key("a key")]),
+            ("$['a.b'][ 1 ]", vec![key("a.b"), Index(1)]),
+            (r#"$["1"]"#, vec![key("1")]),
+            (" $[0] ", vec![Index(0)]),
+        ];
+        for (path, expected) in cases {
+            let parsed: JsonPath = path.parse().unwrap();
+            assert_eq!(parsed.segments, expected, "parse `{}`", path);
+            assert_eq!(parsed.to_string(), path);
+        }
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_paths() {
+        let cases = [
+            ("", "expected key"),
+            ("$", "path selects the whole document"),
+            ("a.", "expected key after `.`"),
+            ("$..", "expected key after `..`"),
+            ("a[0", "missing `]`"),
+            ("a[b]", "expected index, `*` or quoted key inside `[]`"),
+            ("a[-1]", "expected index, `*` or quoted key inside `[]`"),
+            ("$a", "expected `.` or `[`"),
+            ("a]", "expected key"),
+        ];
+        for (path, expected) in cases {
+            let err = path.parse::<JsonPath>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("invalid path `{}`: {}", path, expected)
+            );
+        }
+    }
+
+    #[test]
+    fn remove_drops_every_matched_node() {
+        let cases = [
+            (json!({"a": 1, "b": 2}), "a", json!({"b": 2})),
+            (
+                json!({"a": {"b": 1, "c": 2}}),
+                "a.b",
+                json!({"a": {"c": 2}}),
+            ),
+            (json!({"a": 1}), "a.b.c", json!({"a": 1})),
+            (
+                json!({"items": [0, 1, 2]}),
+                "items[1]",
+                json!({"items": [0, 2]}),
+            ),
+            (json!({"items": [0]}), "items[5]", json!({"items": [0]})),
+            (
+                json!({"items": [{"id": 1, "at": 2}, {"id": 3, "at": 4}]}),
+                "items[*].at",
+                json!({"items": [{"id": 1}, {"id": 3}]}),
+            ),
+            (
+                json!({"items": [1, 2], "n": 3}),
+                "items[*]",
+                json!({"items": [], "n": 3}),
+            ),
+            (json!({"a": {"b": 1}}), "a.*", json!({"a": {}})),
+            (
+                json!({"trace_id": 1, "data": {"trace_id": 2, "list": [{"trace_id": 3, "v": 4}]}}),
+                "$..trace_id",
+                json!({"data": {"list": [{"v": 4}]}}),
+            ),
+            (
+                json!({"data": {"meta": {"id": 1, "etag": 2}, "items": [{"meta": {"id": 3}}]}}),
+                "data..meta.id",
+                json!({"data": {"meta": {"etag": 2}, "items": [{"meta": {}}]}}),
+            ),
+            (
+                json!({"a key": 1, "b": 2}),
+                r#"$["a key"]"#,
+                json!({"b": 2}),
+            ),
+        ];
+        for (mut doc, path, expected) in cases {
+            let path: JsonPath = path.parse().unwrap();
+            path.remove(&mut doc);
+            assert_eq!(doc, expected, "remove `{}`", path);
+        }
+    }
+
+    #[test]
+    fn set_creates_and_replaces_nodes() {
+        let cases = [
+            (json!({}), "a", json!({"a": 1})),
+            (json!(null), "a.b", json!({"a": {"b": 1}})),
+            (
+                json!({"a": {"b": 0, "c": 2}}),
+                "a.b",
+                json!({"a": {"b": 1, "c": 2}}),
+            ),
+            (json!({}), "items[0]", json!({"items": [1]})),
+            (json!({"items": [0]}), "items[0]", json!({"items": [1]})),
+            (json!({"items": [0]}), "items[1]", json!({"items": [0, 1]})),
+            (json!({}), "items[0].qty", json!({"items": [{"qty": 1}]})),
+            (json!([0]), "$[1]", json!([0, 1])),
+            (json!({}), r#"$["a key"]"#, json!({"a key": 1})),
+        ];
+        for (mut doc, path, expected) in cases {
+            let path: JsonPath = path.parse().unwrap();
+            path.set(&mut doc, json!(1)).unwrap();
+            assert_eq!(doc, expected, "set `{}`", path);
+        }
+    }
+
+    #[test]
+    fn set_fails_with_the_offending_node() {
+        let cases = [
+            (
+                json!({"items": []}),
+                "items[2]",
+                "cannot set `items[2]`, `items` can only be appended to at index 0",
+            ),
+            (
+                json!({"items": [0]}),
+                "items[1000000]",
+                "cannot set `items[1000000]`, `items` can only be appended to at index 1",
+            ),
+            (
+                json!([]),
+                "$[1]",
+                "cannot set `$[1]`, the document can only be appended to at index 0",
+            ),
+            (
+                json!({"a": 1}),
+                "a.b",
+                "cannot set `a.b`, `a` is not an object",
+            ),
+            (
+                json!({"a": {}}),
+                "a[0]",
+                "cannot set `a[0]`, `a` is not an array",
+            ),
+            (
+                json!([]),
+                "a",
+                "cannot set `a`, the document is not an object",
+            ),
+            (
+                json!({}),
+                "items[*].id",
+                "cannot set `items[*].id`, only keys and indexes can be set",
+            ),
+            (
+                json!({}),
+                "$..id",
+                "cannot set `$..id`, only keys and indexes can be set",
+            ),
+        ];
+        for (mut doc, path, expected) in cases {
+            let path: JsonPath = path.parse().unwrap();
+            let err = path.set(&mut doc, json!(1)).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+}
@@ -1,16 +1,86 @@
 pub mod cli;
 mod config;
+mod diff;
+mod html;
+mod json_diff;
+mod json_path;
+mod report;
 mod utils;
 
 pub use config::{
-    DiffConfig, DiffProfile, LoadConfig, RequestConfig, RequestProfile, ResponseProfile,
-    get_body_text, get_headers_text, get_status_text,
+    Auth, ClientConfig, ConfigFormat, ConfigLocation, ConfigSource, DiffConfig, DiffMode,
+    DiffProfile, EffectiveRequest, FilePart, FilteredResponse, Grant, HttpVersion, Issue, Issues,
+    LoadConfig, Location, NormalizeAction, NormalizeRule, OAuth2, RequestConfig, RequestProfile,
+    ResponseProfile, RetryPolicy, Severity, config_schema, discover_config, get_body_json,
+    get_body_text, get_headers_text, get_status_text, mask_secrets,
+};
+pub use diff::{
+    ConfigError, DiffLine, DiffResult, DiffStats, EXIT_DIFFERENT, EXIT_ERROR, EXIT_IDENTICAL,
+    EXIT_REQUEST_FAILED, ErrorKind, Hunk, LineTag, ProfileRun, RunStatus, SectionDiff,
+};
+pub use html::html_report;
+pub use json_diff::{ChangeKind, JsonChange, diff_json, render_changes};
+pub use json_path::JsonPath;
+pub use report::{ReportFormat, junit_report};
+pub use utils::{
+    diff_text, diff_text_side_by_side, highlight_html_lines, highlight_text, render_table,
 };
-pub use utils::{diff_text, highlight_text};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ExtraArgs {
     pub query: Vec<(String, String)>,
-    pub header: Vec<(String, String)>,
-    pub body: Vec<(String, String)>,
+    /// Headers to set, or to remove when `None`.
+    pub header: Vec<(String, Option<String>)>,
+    /// Body fields by path like `items[0].qty` to set, or to remove when `None`.
+    pub body: Vec<(String, Option<serde_json::Value>)>,
+    /// Files attached to a multipart body, like `./avatar.png;type=image/png`.
+    pub files: Vec<(String, String)>,
+    pub vars: Vec<(String, String)>,
+    /// Overrides of only the first request of a diff, given with `-e 1:key=value`.
+    pub req1: SideArgs,
+    /// Overrides of only the second request of a diff, given with `-e 2:key=value`.
+    pub req2: SideArgs,
+}
+
+/// One of the two requests of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Req1,
+    Req2,
+}
+
+/// Overrides of only one request of a diff. Template variables apply to the whole
+/// config, so they can't be set for one side.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SideArgs {
+    pub query: Vec<(String, String)>,
+    pub header: Vec<(String, Option<String>)>,
+    pub body: Vec<(String, Option<serde_json::Value>)>,
+    pub files: Vec<(String, String)>,
+}
+
+impl ExtraArgs {
+    /// Get the overrides of one request of a diff.
+    /// Overrides of the side are applied after the shared ones, so they take precedence.
+    pub fn side(&self, side: Side) -> ExtraArgs {
+        let own = match side {
+            Side::Req1 => &self.req1,
+            Side::Req2 => &self.req2,
+        };
+        let mut args = ExtraArgs {
+            req1: SideArgs::default(),
+            req2: SideArgs::default(),
+            ..self.clone()
+        };
+        args.query.extend(own.query.iter().cloned());
+        args.header.extend(own.header.iter().cloned());
+        args.body.extend(own.body.iter().cloned());
+        args.files.extend(own.files.iter().cloned());
+        args
+    }
+
+    /// Whether any override only applies to one request of a diff.
+    pub fn has_sides(&self) -> bool {
+        self.req1 != SideArgs::default() || self.req2 != SideArgs::default()
+    }
 }
@@ -0,0 +1,233 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+
+use crate::{ErrorKind, ProfileRun, RunStatus, html_report};
+
+/// Format of a report file written after a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// JUnit XML with one test case per profile.
+    Junit,
+    /// JSON list of profile runs.
+    Json,
+    /// Self-contained HTML page with side-by-side diffs.
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "junit" => Ok(ReportFormat::Junit),
+            "json" => Ok(ReportFormat::Json),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(anyhow!("Unsupported report format: {}", s)),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Render the profile runs in this format.
+    pub fn render(&self, runs: &[ProfileRun]) -> Result<String> {
+        match self {
+            ReportFormat::Junit => junit_report(runs),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(runs)?),
+            ReportFormat::Html => html_report(runs),
+        }
+    }
+}
+
+/// Render profile runs as JUnit XML. Different responses are reported as
+/// failures with the plain text diff, other failures as errors of their kind.
+pub fn junit_report(runs: &[ProfileRun]) -> Result<String> {
+    let count = |status| runs.iter().filter(|r| r.status == status).count();
+    let time: f64 = runs.iter().map(|r| r.elapsed.as_secs_f64()).sum();
+    let counts = format!(
+        r#"tests="{}" failures="{}" errors="{}" time="{:.3}""#,
+        runs.len(),
+        count(RunStatus::Diff),
+        count(RunStatus::Error),
+        time
+    );
+
+    let mut output = String::new();
+    writeln!(&mut output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(&mut output, r#"<testsuites name="rdiff" {}>"#, counts)?;
+    writeln!(&mut output, r#"  <testsuite name="rdiff" {}>"#, counts)?;
+    for run in runs {
+        write!(
+            &mut output,
+            r#"    <testcase name="{}" classname="rdiff" time="{:.3}""#,
+            escape_xml(&run.name),
+            run.elapsed.as_secs_f64()
+        )?;
+        match (run.status, &run.result, &run.error) {
+            (RunStatus::Diff, Some(result), _) => {
+                let message = format!(
+                    "responses differ: {} insertions, {} deletions, {} changes",
+                    result.stats.insertions, result.stats.deletions, result.stats.changes
+                );
+                let diff = console::strip_ansi_codes(&result.render_terminal()?).into_owned();
+                writeln!(&mut output, ">")?;
+                writeln!(
+                    &mut output,
+                    r#"      <failure message="{}" type="diff">{}</failure>"#,
+                    escape_xml(&message),
+                    escape_xml(&diff)
+                )?;
+                writeln!(&mut output, "    </testcase>")?;
+            }
+            (RunStatus::Error, _, Some(error)) => {
+                writeln!(&mut output, ">")?;
+                writeln!(
+                    &mut output,
+                    r#"      <error message="{}" type="{}">{}</error>"#,
+                    escape_xml(error),
+                    run.error_kind.unwrap_or(ErrorKind::Diff),
+                    escape_xml(error)
+                )?;
+                writeln!(&mut output, "    </testcase>")?;
+            }
+            _ => writeln!(&mut output, "/>")?,
+        }
+    }
+    writeln!(&mut output, "  </testsuite>")?;
+    writeln!(&mut output, "</testsuites>")?;
+
+    Ok(output)
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffResult, SectionDiff};
+    use anyhow::anyhow;
+    use regex::Regex;
+    use std::time::Duration;
+
+    /// Check that `xml` is well-formed: tags are balanced, and `<`, `&` and `]]>`
+    /// only appear in markup and entities.
+    fn assert_well_formed(xml: &str) {
+        let prolog = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+        let mut rest = xml.strip_prefix(prolog).expect("missing XML declaration");
+        let tag = Regex::new(
+            r#"^<(/?)([a-z]+)(?: [a-z]+="(?:[^"<&]|&(?:amp|lt|gt|quot|apos);)*")*(/?)>"#,
+        )
+        .unwrap();
+        let text = Regex::new(r"^(?:[^<&]|&(?:amp|lt|gt|quot|apos);)*").unwrap();
+        let mut open = Vec::new();
+        while !rest.is_empty() {
+            let content = text.find(rest).unwrap().as_str();
+            assert!(!content.contains("]]>"), "`]]>` in text: {}", content);
+            rest = &rest[content.len()..];
+            if rest.is_empty() {
+                break;
+            }
+            let caps = tag
+                .captures(rest)
+                .unwrap_or_else(|| panic!("invalid markup: {}", rest));
+            let name = caps[2].to_string();
+            match (&caps[1], &caps[3]) {
+                ("/", "") => assert_eq!(open.pop(), Some(name)),
+                ("", "/") => {}
+                ("", "") => open.push(name),
+                _ => panic!("invalid tag: {}", &caps[0]),
+            }
+            rest = &rest[caps[0].len()..];
+        }
+        assert!(open.is_empty(), "unclosed tags: {:?}", open);
+    }
+
+    fn run(name: &str, result: Result<DiffResult>) -> ProfileRun {
+        ProfileRun::new(name.to_string(), Duration::from_millis(5), result)
+    }
+
+    #[test]
+    fn junit_escapes_bodies_and_errors() {
+        let nasty = "<b>&amp; \"x\" 'y' ]]> \u{1b}[0m";
+        let section = |old: &str, new: &str| SectionDiff::new(old.to_string(), new.to_string());
+        let body = section(&format!("{}\n", nasty), "<b/>\n");
+        let runs = [
+            run(
+                "pass<&>",
+                Ok(DiffResult::new(
+                    section("200\n", "200\n"),
+                    section("", ""),
+                    section("", ""),
+                    None,
+                )),
+            ),
+            run(
+                "diff",
+                Ok(DiffResult::new(
+                    section("200\n", "200\n"),
+                    section("", ""),
+                    body,
+                    None,
+                )),
+            ),
+            run("error", Err(anyhow!("invalid body {}", nasty))),
+        ];
+
+        let xml = junit_report(&runs).unwrap();
+        assert_well_formed(&xml);
+        assert!(
+            xml.contains(r#"<testcase name="pass&lt;&amp;&gt;""#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains("-&lt;b&gt;&amp;amp; &quot;x&quot; &apos;y&apos; ]]&gt;"),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"type="diff">invalid body &lt;b&gt;"#),
+            "{}",
+            xml
+        );
+    }
+
+    #[test]
+    fn junit_error_type_is_the_error_kind() {
+        let config = Err(anyhow!("no such file").context(crate::ConfigError));
+        let mut request = run("request", Err(anyhow!("connection refused")));
+        request.error_kind = Some(ErrorKind::Request);
+        let runs = [
+            run("config", config),
+            request,
+            run("diff", Err(anyhow!("invalid JSON body"))),
+        ];
+
+        let xml = junit_report(&runs).unwrap();
+        assert_well_formed(&xml);
+        for (message, kind) in [
+            ("invalid request: no such file", "config"),
+            ("connection refused", "request"),
+            ("invalid JSON body", "diff"),
+        ] {
+            let error = format!(r#"<error message="{}" type="{}">"#, message, kind);
+            assert!(xml.contains(&error), "{}", xml);
+        }
+    }
+}
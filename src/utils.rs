@@ -1,15 +1,21 @@
 use std::fmt::{self, Write};
 
 use anyhow::Result;
-use console::{Style, style};
+use console::{Style, measure_text_width, style};
 use similar::{ChangeTag, TextDiff};
+use std::sync::LazyLock;
 use syntect::{
     easy::HighlightLines,
     highlighting::ThemeSet,
+    html::{IncludeBackground, styled_line_to_highlighted_html},
     parsing::SyntaxSet,
     util::{LinesWithEndings, as_24_bit_terminal_escaped},
 };
 
+// Loading syntaxes and themes is slow, so do it once.
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
 struct Line(Option<usize>);
 
 impl fmt::Display for Line {
@@ -61,28 +67,323 @@ pub fn diff_text(text1: &str, text2: &str) -> Result<String> {
     Ok(output)
 }
 
+/// Diff two texts as two columns fitting in `width` terminal columns.
+/// Long lines are wrapped and intra-line changes are emphasized.
+pub fn diff_text_side_by_side(text1: &str, text2: &str, width: usize) -> Result<String> {
+    let mut output = String::new();
+    let diff = TextDiff::from_lines(text1, text2);
+    // Each column is made of a line number, a sign and the content.
+    let column = (width.saturating_sub(3) / 2).max(20);
+    let digits = text1
+        .lines()
+        .count()
+        .max(text2.lines().count())
+        .to_string()
+        .len();
+    let content = column.saturating_sub(digits + 3).max(1);
+
+    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+        if idx > 0 {
+            writeln!(&mut output, "{:-^1$}", "-", width)?;
+        }
+        for op in group {
+            let (mut left, mut right) = (Vec::new(), Vec::new());
+            for change in diff.iter_inline_changes(op) {
+                let (line, side) = match change.tag() {
+                    ChangeTag::Delete => (change.old_index(), &mut left),
+                    ChangeTag::Insert => (change.new_index(), &mut right),
+                    ChangeTag::Equal => {
+                        let cell = Cell::new(change.old_index(), &change);
+                        left.push(cell.wrap(digits, content));
+                        (change.new_index(), &mut right)
+                    }
+                };
+                side.push(Cell::new(line, &change).wrap(digits, content));
+            }
+
+            let blank = " ".repeat(column);
+            for i in 0..left.len().max(right.len()) {
+                let (l, r) = (left.get(i), right.get(i));
+                let rows = l.map_or(0, Vec::len).max(r.map_or(0, Vec::len));
+                for j in 0..rows {
+                    writeln!(
+                        &mut output,
+                        "{} {} {}",
+                        l.and_then(|l| l.get(j)).unwrap_or(&blank),
+                        style("|").dim(),
+                        r.and_then(|r| r.get(j)).map_or("", |r| r.trim_end())
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// One side of a side-by-side diff row.
+struct Cell {
+    line: Option<usize>,
+    tag: ChangeTag,
+    chars: Vec<(bool, char)>,
+}
+
+impl Cell {
+    fn new<T: similar::DiffableStr + ?Sized>(
+        line: Option<usize>,
+        change: &similar::InlineChange<'_, T>,
+    ) -> Self {
+        let chars = change
+            .iter_strings_lossy()
+            .flat_map(|(emphasized, value)| {
+                value
+                    .replace('\t', "    ")
+                    .chars()
+                    .filter(|c| !matches!(c, '\r' | '\n'))
+                    .map(|c| (emphasized, c))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Self {
+            line,
+            tag: change.tag(),
+            chars,
+        }
+    }
+
+    /// Render the cell as rows of exactly `width` content columns, after a line
+    /// number padded to `digits` columns.
+    fn wrap(&self, digits: usize, width: usize) -> Vec<String> {
+        let (sign, s) = match self.tag {
+            ChangeTag::Delete => ("-", Style::new().red()),
+            ChangeTag::Insert => ("+", Style::new().green()),
+            ChangeTag::Equal => (" ", Style::new().dim()),
+        };
+        let emphasis = s.clone().underlined().on_black();
+        // Style runs of characters with the same emphasis at once, rather than each
+        // character, to keep the escape sequences short.
+        let flush = |row: &mut String, run: &mut String, emphasized: bool| {
+            if !run.is_empty() {
+                let style = if emphasized { &emphasis } else { &s };
+                row.push_str(&style.apply_to(std::mem::take(run)).to_string());
+            }
+        };
+
+        let mut rows = Vec::new();
+        let mut row = String::new();
+        let mut run = String::new();
+        let mut run_emphasized = false;
+        let mut used = 0;
+        for &(emphasized, c) in &self.chars {
+            let w = measure_text_width(c.encode_utf8(&mut [0; 4]));
+            if used + w > width {
+                flush(&mut row, &mut run, run_emphasized);
+                rows.push((std::mem::take(&mut row), used));
+                used = 0;
+            }
+            if emphasized != run_emphasized {
+                flush(&mut row, &mut run, run_emphasized);
+                run_emphasized = emphasized;
+            }
+            run.push(c);
+            used += w;
+        }
+        flush(&mut row, &mut run, run_emphasized);
+        rows.push((row, used));
+
+        rows.into_iter()
+            .enumerate()
+            .map(|(idx, (row, used))| {
+                let (line, sign) = match (idx, self.line) {
+                    (0, Some(line)) => (format!("{:<1$}", line + 1, digits), sign),
+                    (0, None) => (" ".repeat(digits), sign),
+                    _ => (" ".repeat(digits), " "),
+                };
+                format!(
+                    "{} {} {}{}",
+                    style(line).dim(),
+                    s.apply_to(sign).bold(),
+                    row,
+                    " ".repeat(width.saturating_sub(used))
+                )
+            })
+            .collect()
+    }
+}
+
+/// Render rows as a table with a bold header, padding every column to its widest cell.
+pub fn render_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| measure_text_width(cell))
+                .chain(std::iter::once(header[i].len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let pad = width.saturating_sub(measure_text_width(cell));
+                format!("{}{}", cell, " ".repeat(pad))
+            })
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut output = format!("{}\n", style(line(header.to_vec())).bold());
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+        output.push('\n');
+    }
+    output
+}
+
 /// Do syntax highlighting on `text` with syntax extention name.
 pub fn highlight_text(text: &str, extention: &str, theme: Option<&str>) -> Result<String> {
-    // Load these once at the start of your program
-    let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-
-    // Find syntax extension or use plain text.
-    let syntax = ps
-        .find_syntax_by_extension(extention)
-        .unwrap_or_else(|| ps.find_syntax_plain_text());
-    let mut h = HighlightLines::new(
-        syntax,
-        &ts.themes[theme.unwrap_or_else(|| "base16-ocean.dark")],
-    );
+    let ps = &*SYNTAX_SET;
+    let mut h = highlighter(extention, theme);
 
     let mut output = String::new();
     for line in LinesWithEndings::from(text) {
-        let ranges: Vec<(syntect::highlighting::Style, &str)> =
-            h.highlight_line(line, &ps).unwrap();
+        let ranges: Vec<(syntect::highlighting::Style, &str)> = h.highlight_line(line, ps)?;
         let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
         write!(&mut output, "{}", escaped)?;
     }
 
     Ok(output)
 }
+
+/// Do syntax highlighting on `lines` and return one HTML fragment with inline styles per line.
+/// Lines are taken as given, e.g. the slices of a `TextDiff`, which also end at a lone `\r`.
+pub fn highlight_html_lines(
+    lines: &[&str],
+    extention: &str,
+    theme: Option<&str>,
+) -> Result<Vec<String>> {
+    let ps = &*SYNTAX_SET;
+    let mut h = highlighter(extention, theme);
+
+    let mut html_lines = Vec::with_capacity(lines.len());
+    for line in lines {
+        let ranges = h.highlight_line(line, ps)?;
+        let html = styled_line_to_highlighted_html(&ranges[..], IncludeBackground::No)?;
+        html_lines.push(html.replace(['\r', '\n'], ""));
+    }
+
+    Ok(html_lines)
+}
+
+fn highlighter(extention: &str, theme: Option<&str>) -> HighlightLines<'static> {
+    // Find syntax extension or use plain text.
+    let syntax = SYNTAX_SET
+        .find_syntax_by_extension(extention)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    HighlightLines::new(
+        syntax,
+        &THEME_SET.themes[theme.unwrap_or("base16-ocean.dark")],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Visible text of a rendered row, with underlined (emphasized) runs in brackets.
+    fn emphasis(row: &str) -> String {
+        let mut text = String::new();
+        let mut underlined = false;
+        let mut rest = row;
+        while let Some(c) = rest.chars().next() {
+            if let Some(codes) = rest.strip_prefix("\x1b[") {
+                let end = codes.find('m').unwrap();
+                for code in codes[..end].split(';') {
+                    if (code == "4" && !underlined) || (code == "0" && underlined) {
+                        underlined = !underlined;
+                        text.push(if underlined { '[' } else { ']' });
+                    }
+                }
+                rest = &codes[end + 1..];
+            } else {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn wrap_keeps_emphasis_on_the_same_characters() {
+        console::set_colors_enabled(true);
+        // Characters of a line, emphasized in brackets.
+        let chars = |s: &str| {
+            let mut emphasized = false;
+            let mut chars = Vec::new();
+            for c in s.chars() {
+                match c {
+                    '[' | ']' => emphasized = c == '[',
+                    c => chars.push((emphasized, c)),
+                }
+            }
+            chars
+        };
+        let cases = [
+            ("abc", 5, vec!["1 + abc  "]),
+            (
+                "abcdefg[hij]klm",
+                5,
+                vec!["1 + abcde", "    fg[hij]", "    klm  "],
+            ),
+            (
+                "ab[cdefghij]k",
+                4,
+                vec!["1 + ab[cd]", "    [efgh]", "    [ij]k "],
+            ),
+            // Wide characters are not split, the row is padded instead.
+            ("[界界]a界", 3, vec!["1 + [界] ", "    [界]a", "    界 "]),
+        ];
+        for (line, width, expected) in cases {
+            let cell = Cell {
+                line: Some(0),
+                tag: ChangeTag::Insert,
+                chars: chars(line),
+            };
+            let rows = cell.wrap(1, width);
+            let rows: Vec<String> = rows.iter().map(|row| emphasis(row)).collect();
+            assert_eq!(rows, expected, "wrap `{}` at {}", line, width);
+        }
+    }
+
+    #[test]
+    fn side_by_side_wraps_long_lines_to_the_column() {
+        let old = format!("{}\nsame\n", "a".repeat(50));
+        let new = format!("{}\nsame\n", "b".repeat(50));
+        let width = 60;
+        let output = diff_text_side_by_side(&old, &new, width).unwrap();
+        let rows: Vec<String> = output
+            .lines()
+            .map(|row| console::strip_ansi_codes(row).into_owned())
+            .collect();
+
+        // Columns are 28 wide: 1 digit, a sign and 24 characters of content.
+        assert_eq!(rows.len(), 4, "{:#?}", rows);
+        for row in &rows {
+            assert_eq!(row.find('|'), Some(29), "{:?}", row);
+            assert!(measure_text_width(row) <= width, "{:?}", row);
+        }
+        let side = |i: usize| -> String {
+            rows.iter()
+                .take(3)
+                .map(|row| row.split(" | ").nth(i).unwrap()[4..].trim_end().to_string())
+                .collect()
+        };
+        assert_eq!(side(0), "a".repeat(50));
+        assert_eq!(side(1), "b".repeat(50));
+        assert!(rows[3].starts_with("2   same"), "{:?}", rows[3]);
+    }
+}
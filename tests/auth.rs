@@ -0,0 +1,190 @@
+//! OAuth2 tokens of `auth`, fetched from a mock token endpoint.
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rdiff::{ExtraArgs, RequestProfile};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by the mock server.
+#[derive(Debug, Clone)]
+struct Received {
+    path: String,
+    body: String,
+}
+
+/// A server answering `/token` with a new token each time, and any other path with
+/// the `authorization` header it was sent.
+struct Mock {
+    url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Mock {
+    /// Start a server whose tokens have this `token_type` and `expires_in`.
+    async fn start(token_type: Option<&str>, expires_in: Option<u64>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut fields = Vec::new();
+        if let Some(token_type) = token_type {
+            fields.push(format!(r#""token_type":"{}""#, token_type));
+        }
+        if let Some(expires_in) = expires_in {
+            fields.push(format!(r#""expires_in":{}"#, expires_in));
+        }
+        let log = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (log, fields) = (log.clone(), fields.clone());
+                tokio::spawn(async move { respond(stream, log, fields).await });
+            }
+        });
+
+        Self { url, received }
+    }
+
+    /// Requests of the token endpoint received so far.
+    fn token_requests(&self) -> Vec<Received> {
+        let received = self.received.lock().unwrap();
+        received
+            .iter()
+            .filter(|r| r.path == "/token")
+            .cloned()
+            .collect()
+    }
+
+    /// Build a request of the mock authenticated with `auth`, a YAML mapping.
+    fn request(&self, auth: &str) -> RequestProfile {
+        let yaml = format!(
+            "url: {url}/echo\nauth: {{type: oauth2, token_url: {url}/token, {auth}}}",
+            url = self.url,
+            auth = auth
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+}
+
+async fn respond(mut stream: TcpStream, log: Arc<Mutex<Vec<Received>>>, fields: Vec<String>) {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    let (head, body) = loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return;
+        }
+        data.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&data).to_string();
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map_or(0, |(_, value)| value.trim().parse().unwrap());
+        if body.len() >= length {
+            break (head.to_string(), body.to_string());
+        }
+    };
+
+    let path = head.split(' ').nth(1).unwrap().to_string();
+    let response = {
+        let mut log = log.lock().unwrap();
+        log.push(Received {
+            path: path.clone(),
+            body,
+        });
+        if path == "/token" {
+            let count = log.iter().filter(|r| r.path == "/token").count();
+            let mut fields = fields;
+            fields.push(format!(r#""access_token":"tok{}""#, count));
+            format!("{{{}}}", fields.join(","))
+        } else {
+            head.lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+                .map(|(_, value)| value.trim().to_string())
+                .unwrap_or_default()
+        }
+    };
+    let response = format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        response.len(),
+        response
+    );
+    stream.write_all(response.as_bytes()).await.unwrap();
+}
+
+/// Send a request and get the `authorization` header the mock received.
+async fn authorization(req: &RequestProfile) -> String {
+    let resp = req.send(&ExtraArgs::default()).await.unwrap();
+    resp.get_inner().text().await.unwrap()
+}
+
+#[tokio::test]
+async fn token_is_fetched_once_until_it_expires() {
+    let mock = Mock::start(Some("Bearer"), Some(3600)).await;
+    let req = mock.request("client_id: cli, client_secret: s3cret, scope: read");
+
+    assert_eq!(authorization(&req).await, "Bearer tok1");
+    assert_eq!(authorization(&req).await, "Bearer tok1");
+
+    let requests = mock.token_requests();
+    assert_eq!(requests.len(), 1);
+    let form = &requests[0].body;
+    assert!(form.contains("grant_type=client_credentials"), "{}", form);
+    assert!(form.contains("client_id=cli"), "{}", form);
+    assert!(form.contains("client_secret=s3cret"), "{}", form);
+    assert!(form.contains("scope=read"), "{}", form);
+}
+
+#[tokio::test]
+async fn concurrent_requests_share_one_token() {
+    let mock = Mock::start(Some("Bearer"), Some(3600)).await;
+    let req = mock.request("client_id: concurrent");
+
+    let (a, b) = tokio::join!(authorization(&req), authorization(&req));
+    assert_eq!((a.as_str(), b.as_str()), ("Bearer tok1", "Bearer tok1"));
+    assert_eq!(mock.token_requests().len(), 1);
+}
+
+#[tokio::test]
+async fn token_is_refreshed_before_it_expires() {
+    let mock = Mock::start(Some("Bearer"), Some(1)).await;
+    let req = mock.request("client_id: cli");
+
+    assert_eq!(authorization(&req).await, "Bearer tok1");
+    // Short-lived tokens are refreshed after half their lifetime.
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(authorization(&req).await, "Bearer tok2");
+    assert_eq!(authorization(&req).await, "Bearer tok2");
+    assert_eq!(mock.token_requests().len(), 2);
+}
+
+#[tokio::test]
+async fn password_grant_sends_the_user_credentials() {
+    let mock = Mock::start(Some("Bearer"), None).await;
+    let req = mock.request("grant: password, client_id: cli, username: alice, password: pw");
+
+    assert_eq!(authorization(&req).await, "Bearer tok1");
+    let form = &mock.token_requests()[0].body;
+    assert!(form.contains("grant_type=password"), "{}", form);
+    assert!(form.contains("username=alice"), "{}", form);
+    assert!(form.contains("password=pw"), "{}", form);
+}
+
+#[tokio::test]
+async fn token_type_defaults_to_bearer() {
+    for (token_type, expected) in [
+        (None, "Bearer tok1"),
+        (Some("bearer"), "Bearer tok1"),
+        (Some("MAC"), "MAC tok1"),
+    ] {
+        let mock = Mock::start(token_type, None).await;
+        let req = mock.request("client_id: cli");
+        assert_eq!(authorization(&req).await, expected, "{:?}", token_type);
+    }
+}
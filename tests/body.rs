@@ -0,0 +1,157 @@
+//! Bodies of requests as they are sent, and of responses as they are read.
+use rdiff::{ExtraArgs, LoadConfig, RequestConfig, RequestProfile, get_body_json, get_body_text};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A server answering with the body it was sent, as `content-type` and the body on
+/// separate lines, or with the body and the content type of the `x-reply-type` header.
+async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(respond(stream));
+        }
+    });
+    url
+}
+
+async fn respond(mut stream: TcpStream) {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    let (head, body) = loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return;
+        }
+        data.extend_from_slice(&buf[..n]);
+        let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&data[..end]).to_string();
+        let length = header(&head, "content-length").map_or(0, |v| v.parse().unwrap());
+        if data.len() - end - 4 >= length {
+            break (head, data[end + 4..].to_vec());
+        }
+    };
+
+    let (content_type, body) = match header(&head, "x-reply-type") {
+        Some(reply_type) => (reply_type, body),
+        None => {
+            let mut echo = header(&head, "content-type")
+                .unwrap_or_default()
+                .into_bytes();
+            echo.push(b'\n');
+            echo.extend(body);
+            ("text/plain".to_string(), echo)
+        }
+    };
+    let head = format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&body).await.unwrap();
+}
+
+fn header(head: &str, name: &str) -> Option<String> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// Send a request, a YAML mapping without its `url`, and get the response bytes.
+async fn send(url: &str, request: &str) -> Vec<u8> {
+    let yaml = format!("url: {}/echo\n{}", url, request);
+    let req: RequestProfile = serde_yaml::from_str(&yaml).unwrap();
+    let resp = req.send(&ExtraArgs::default()).await.unwrap();
+    resp.get_inner().bytes().await.unwrap().to_vec()
+}
+
+#[tokio::test]
+async fn bodies_are_sent_as_is_with_a_default_content_type() {
+    let url = start().await;
+    let file = std::env::temp_dir().join(format!("rdiff-body-{}.bin", std::process::id()));
+    std::fs::write(&file, [0, 159, 146, 150]).unwrap();
+
+    let cases: Vec<(String, &[u8])> = vec![
+        ("body_raw: '<a>1</a>'".into(), b"text/plain\n<a>1</a>"),
+        (
+            "body_raw: '<a/>'\nheaders: {content-type: application/xml}".into(),
+            b"application/xml\n<a/>",
+        ),
+        (
+            format!("body_file: {}", file.display()),
+            b"application/octet-stream\n\x00\x9f\x92\x96",
+        ),
+        (
+            "body_base64: \"AJ+S\n  lg==\"".into(),
+            b"application/octet-stream\n\x00\x9f\x92\x96",
+        ),
+        ("body: {a: 1}".into(), b"application/json\n{\"a\":1}"),
+    ];
+    for (request, expected) in cases {
+        assert_eq!(send(&url, &request).await, expected, "{}", request);
+    }
+}
+
+#[tokio::test]
+async fn json_content_types_are_parsed() {
+    let url = start().await;
+    let body = r#"{"id": 1, "name": "a"}"#;
+    let cases = [
+        ("application/json", json!({"name": "a"})),
+        (
+            "application/problem+json; charset=utf-8",
+            json!({"name": "a"}),
+        ),
+        ("Application/JSON", json!({"name": "a"})),
+        ("text/plain", json!(body)),
+    ];
+    for (content_type, expected) in cases {
+        let yaml = format!(
+            "url: {}/echo\nheaders: {{x-reply-type: '{}', content-type: text/plain}}\nbody_raw: '{}'",
+            url, content_type, body
+        );
+        let req: RequestProfile = serde_yaml::from_str(&yaml).unwrap();
+        let skip = ["id".to_string()];
+
+        let resp = req.send(&ExtraArgs::default()).await.unwrap();
+        let json = get_body_json(resp.get_inner(), &skip).await.unwrap();
+        assert_eq!(json, expected, "{}", content_type);
+
+        let resp = req.send(&ExtraArgs::default()).await.unwrap();
+        let text = get_body_text(resp.get_inner(), &skip).await.unwrap();
+        let expected = match expected.as_str() {
+            Some(text) => text.to_string(),
+            None => serde_json::to_string_pretty(&expected).unwrap(),
+        };
+        assert_eq!(text, expected, "{}", content_type);
+    }
+}
+
+#[test]
+fn only_one_body_source_is_allowed() {
+    let err = RequestConfig::from_yaml(
+        r#"
+p:
+  url: "http://a.test"
+  method: POST
+  body: {a: 1}
+  body_raw: text
+  body_base64: AA==
+"#,
+    )
+    .unwrap_err();
+    let message = format!("{:#}", err);
+    for expected in [
+        "p.body_raw: conflicts with `body`, only one body can be set",
+        "p.body_base64: conflicts with `body`, only one body can be set",
+    ] {
+        assert!(message.contains(expected), "{}", message);
+    }
+}
@@ -0,0 +1,90 @@
+//! Retries of `retry`, against a mock answering with a sequence of statuses.
+use std::sync::{Arc, Mutex};
+
+use rdiff::{ExtraArgs, RequestProfile};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A server answering each request with the next of `statuses`, then with 200.
+struct Mock {
+    url: String,
+    requests: Arc<Mutex<usize>>,
+}
+
+impl Mock {
+    async fn start(statuses: &[u16]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(0));
+
+        let (count, statuses) = (requests.clone(), statuses.to_vec());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut data = Vec::new();
+                let mut buf = [0; 4096];
+                // Read the head and the body of its content-length.
+                loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    data.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        if n == 0 {
+                            break;
+                        }
+                        continue;
+                    };
+                    let length = head
+                        .lines()
+                        .filter_map(|line| line.split_once(':'))
+                        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                        .map_or(0, |(_, value)| value.trim().parse().unwrap());
+                    if n == 0 || body.len() >= length {
+                        break;
+                    }
+                }
+                let status = {
+                    let mut count = count.lock().unwrap();
+                    *count += 1;
+                    statuses.get(*count - 1).copied().unwrap_or(200)
+                };
+                let response = format!(
+                    "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Send a request retried with `retry`, entries of a YAML mapping, and get the final status.
+    async fn send(&self, retry: &str) -> u16 {
+        let yaml = format!("url: {}\nretry: {{backoff: 0, {}}}", self.url, retry);
+        let req: RequestProfile = serde_yaml::from_str(&yaml).unwrap();
+        let resp = req.send(&ExtraArgs::default()).await.unwrap();
+        resp.get_inner().status().as_u16()
+    }
+
+    fn requests(&self) -> usize {
+        *self.requests.lock().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn retries_only_the_configured_statuses() {
+    let cases = [
+        ("max_attempts: 3", vec![503, 502], 200, 3),
+        ("max_attempts: 3", vec![500], 500, 1),
+        ("max_attempts: 2", vec![503, 503, 503], 503, 2),
+        ("max_attempts: 3, on_status: [429]", vec![429, 503], 503, 2),
+        ("max_attempts: 3, on_status: []", vec![502], 502, 1),
+        ("jitter: true", vec![503], 503, 1),
+    ];
+    for (retry, statuses, status, requests) in cases {
+        let mock = Mock::start(&statuses).await;
+        assert_eq!(mock.send(retry).await, status, "{} {:?}", retry, statuses);
+        assert_eq!(mock.requests(), requests, "{} {:?}", retry, statuses);
+    }
+}